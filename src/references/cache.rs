@@ -4,9 +4,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use anyhow::Context;
-
-use crate::references::parser::ProcessedFile;
+use crate::references::{error::ReferencesError, parser::ProcessedFile};
 
 pub trait Cache {
     fn get(&self, path: &Path) -> anyhow::Result<CacheResult>;
@@ -25,39 +23,55 @@ pub enum CacheResult {
 
 #[derive(Debug, Default)]
 pub struct EmptyCacheEntry {
-    pub filepath: PathBuf,
     pub file_contents_digest: String,
-    pub file_name_digest: String,
+    pub settings_digest: String,
     pub cache_file_path: PathBuf,
 }
 
+// Bump whenever the serialized shape of CacheEntry, or of anything it contains, changes,
+// and whenever the same file would be processed into something different.
+// Entries are stored under a directory named after the version, so entries written with
+// another shape are never read back.
+pub(crate) const CACHE_FORMAT_VERSION: u32 = 3;
+
 // This function is used to generate the cache file path from the digest of the file name
 // The cache file path is a directory structure with the first two characters of the digest as the directory name
-// and the rest of the digest as the file name
+// and the rest of the digest as the file name, under a directory for CACHE_FORMAT_VERSION
 fn cache_file_path_from_digest(cache_directory: &Path, file_name_digest: &str) -> PathBuf {
-    let cached_directory_for_digest = cache_directory.join(&file_name_digest[..2]);
+    let cached_directory_for_digest = cache_directory
+        .join(format!("v{}", CACHE_FORMAT_VERSION))
+        .join(&file_name_digest[..2]);
     cached_directory_for_digest.join(&file_name_digest[2..])
 }
 
 impl EmptyCacheEntry {
-    pub fn new(cache_directory: &Path, filepath: &Path) -> anyhow::Result<EmptyCacheEntry> {
-        let file_digest = md5::compute(filepath.to_str().unwrap());
+    pub fn new(
+        cache_directory: &Path,
+        filepath: &Path,
+        settings_digest: &str,
+    ) -> anyhow::Result<EmptyCacheEntry> {
+        let file_digest = md5::compute(filepath.to_string_lossy().as_bytes());
         let file_name_digest = format!("{:x}", file_digest);
         let cache_file_path = cache_file_path_from_digest(cache_directory, &file_name_digest);
 
         let file_contents_digest = file_content_digest(filepath)?;
 
         Ok(EmptyCacheEntry {
-            filepath: filepath.to_owned(),
             file_contents_digest,
+            settings_digest: settings_digest.to_owned(),
             cache_file_path,
-            file_name_digest,
         })
     }
 }
 
 pub fn create_cache_dir_idempotently(cache_dir: &Path) -> anyhow::Result<()> {
-    std::fs::create_dir_all(cache_dir).context("Failed to create cache directory")
+    std::fs::create_dir_all(cache_dir).map_err(|source| {
+        ReferencesError::CacheIo {
+            path: cache_dir.to_path_buf(),
+            source,
+        }
+        .into()
+    })
 }
 
 pub struct NoopCache {}
@@ -82,11 +96,12 @@ pub(crate) fn file_content_digest(file: &Path) -> anyhow::Result<String> {
     let mut file_content = Vec::new();
 
     // Read the file content
-    let mut file_handle =
-        fs::File::open(file).context(format!("Failed to open file {:?}", file))?;
-    file_handle
-        .read_to_end(&mut file_content)
-        .context(format!("Failed to read file {:?}", file))?;
+    fs::File::open(file)
+        .and_then(|mut file_handle| file_handle.read_to_end(&mut file_content))
+        .map_err(|source| ReferencesError::UnreadableFile {
+            path: file.to_path_buf(),
+            source,
+        })?;
 
     // Compute the MD5 digest
     Ok(format!("{:x}", md5::compute(&file_content)))
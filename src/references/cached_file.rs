@@ -10,24 +10,28 @@ use tracing::warn;
 use crate::references::cache::create_cache_dir_idempotently;
 use crate::references::cache::CacheResult;
 use crate::references::cache::EmptyCacheEntry;
+use crate::references::error::ReferencesError;
 use crate::references::parser::ProcessedFile;
 
 use super::cache::Cache;
 
 pub struct CachedFile {
     pub cache_dir: PathBuf,
+    // See Configuration::cache_settings_digest
+    pub settings_digest: String,
 }
 
 impl Cache for CachedFile {
     fn get(&self, path: &Path) -> anyhow::Result<CacheResult> {
-        let empty_cache_entry = EmptyCacheEntry::new(&self.cache_dir, path)
+        let empty_cache_entry = EmptyCacheEntry::new(&self.cache_dir, path, &self.settings_digest)
             .context(format!("Failed to create cache entry for {:?}", path))?;
         let cache_entry = CacheEntry::from_empty(&empty_cache_entry)?;
         if let Some(cache_entry) = cache_entry {
-            let file_digests_match =
-                cache_entry.file_contents_digest == empty_cache_entry.file_contents_digest;
+            let digests_match = cache_entry.file_contents_digest
+                == empty_cache_entry.file_contents_digest
+                && cache_entry.settings_digest == empty_cache_entry.settings_digest;
 
-            if !file_digests_match {
+            if !digests_match {
                 Ok(CacheResult::Miss(empty_cache_entry))
             } else {
                 let processed_file = cache_entry.processed_file;
@@ -44,9 +48,11 @@ impl Cache for CachedFile {
         processed_file: &ProcessedFile,
     ) -> anyhow::Result<()> {
         let file_contents_digest = empty_cache_entry.file_contents_digest.to_owned();
+        let settings_digest = empty_cache_entry.settings_digest.to_owned();
 
         let cache_entry = &CacheEntry {
             file_contents_digest,
+            settings_digest,
             // Ideally we could pass by reference here, but in practice this cost should be paid on few files
             // that have changed and need to be reprocessed.
             processed_file: processed_file.clone(),
@@ -54,6 +60,10 @@ impl Cache for CachedFile {
 
         let cache_data =
             serde_json::to_string(&cache_entry).context("Failed to serialize references")?;
+        let cache_io_error = |source| ReferencesError::CacheIo {
+            path: empty_cache_entry.cache_file_path.to_owned(),
+            source,
+        };
         let mut file = match File::create(&empty_cache_entry.cache_file_path) {
             Ok(file) => file,
             Err(_e) => {
@@ -62,13 +72,12 @@ impl Cache for CachedFile {
                     empty_cache_entry.cache_file_path
                 ))?;
                 create_cache_dir_idempotently(parent_dir)?;
-                File::create(&empty_cache_entry.cache_file_path)
-                    .context("failed to create cache file")?
+                File::create(&empty_cache_entry.cache_file_path).map_err(cache_io_error)?
             }
         };
 
         file.write_all(cache_data.as_bytes())
-            .context("Failed to write cache file")?;
+            .map_err(cache_io_error)?;
         Ok(())
     }
 }
@@ -76,6 +85,7 @@ impl Cache for CachedFile {
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct CacheEntry {
     pub file_contents_digest: String,
+    pub settings_digest: String,
     pub processed_file: ProcessedFile,
 }
 
@@ -111,6 +121,7 @@ mod tests {
     use crate::references::{
         cache::file_content_digest,
         parser::{Range, UnresolvedReference},
+        reference::ReferenceKind,
     };
 
     use super::*;
@@ -131,25 +142,35 @@ mod tests {
     }
 
     #[test]
-    fn test_compatible_with_packwerk() {
+    fn test_deserialize_cache_entry() {
         let contents: String = String::from(
             r#"{
   "file_contents_digest":"8f9efdcf2caa22fb7b1b4a8274e68d11",
+  "settings_digest":"d41d8cd98f00b204e9800998ecf8427e",
   "processed_file": {
     "absolute_path":"/tests/fixtures/simple_app/packs/foo/app/services/bar/foo.rb",
     "unresolved_references":[
       {
         "name":"Bar",
         "namespace_path":["Foo","Bar"],
-        "location":{"start_row":8,"start_col":22,"end_row":8,"end_col":25}
+        "location":{
+          "start_row":8,"start_col":22,"end_row":8,"end_col":25,
+          "start_byte":120,"end_byte":123,"start_utf16_col":22,"end_utf16_col":25
+        },
+        "kind":"Constant"
       }],
-    "definitions":[]
+    "definitions":[],
+    "diagnostics":[],
+    "skipped_constructs":[],
+    "mixins":[],
+    "superclasses":[]
   }
 }"#,
         );
 
         let expected_serialized = CacheEntry {
             file_contents_digest: "8f9efdcf2caa22fb7b1b4a8274e68d11".to_owned(),
+            settings_digest: "d41d8cd98f00b204e9800998ecf8427e".to_owned(),
             processed_file: ProcessedFile {
                 absolute_path: PathBuf::from(
                     "/tests/fixtures/simple_app/packs/foo/app/services/bar/foo.rb",
@@ -162,8 +183,18 @@ mod tests {
                         start_col: 22,
                         end_row: 8,
                         end_col: 25,
+                        start_byte: 120,
+                        end_byte: 123,
+                        start_utf16_col: 22,
+                        end_utf16_col: 25,
                     },
+                    kind: ReferenceKind::Constant,
                 }],
+                definitions: vec![],
+                diagnostics: vec![],
+                skipped_constructs: vec![],
+                mixins: vec![],
+                superclasses: vec![],
             },
         };
 
@@ -174,6 +205,77 @@ mod tests {
         teardown();
     }
 
+    #[test]
+    fn test_entries_from_older_formats_are_misses() -> anyhow::Result<()> {
+        let cache_dir = PathBuf::from("tests/fixtures/simple_app/tmp/cache/older-formats");
+        let _ = fs::remove_dir_all(&cache_dir);
+        let file_path =
+            PathBuf::from("tests/fixtures/simple_app/packs/foo/app/services/foo/bar.rb");
+        let file_contents_digest = file_content_digest(&file_path)?;
+        let older_contents = format!(
+            r#"{{
+  "file_contents_digest":"{}",
+  "processed_file": {{
+    "absolute_path":"{}",
+    "unresolved_references":[]
+  }}
+}}"#,
+            file_contents_digest,
+            file_path.display()
+        );
+
+        let empty_cache_entry = EmptyCacheEntry::new(&cache_dir, &file_path, "")?;
+        let file_name = empty_cache_entry.cache_file_path.file_name().unwrap();
+        let prefix = empty_cache_entry.cache_file_path.parent().unwrap();
+        let unversioned_path = cache_dir.join(prefix.file_name().unwrap()).join(file_name);
+        for path in [&unversioned_path, &empty_cache_entry.cache_file_path] {
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, &older_contents)?;
+        }
+
+        let cached_file = CachedFile {
+            cache_dir: cache_dir.clone(),
+            settings_digest: String::new(),
+        };
+        assert!(matches!(cached_file.get(&file_path)?, CacheResult::Miss(_)));
+
+        fs::remove_dir_all(&cache_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_entries_for_other_settings_are_misses() -> anyhow::Result<()> {
+        let cache_dir = PathBuf::from("tests/fixtures/simple_app/tmp/cache/other-settings");
+        let _ = fs::remove_dir_all(&cache_dir);
+        let file_path =
+            PathBuf::from("tests/fixtures/simple_app/packs/foo/app/services/foo/bar.rb");
+        let cached_file = |settings_digest: &str| CachedFile {
+            cache_dir: cache_dir.clone(),
+            settings_digest: settings_digest.to_owned(),
+        };
+
+        let CacheResult::Miss(empty_cache_entry) = cached_file("before").get(&file_path)? else {
+            panic!("expected a cache miss");
+        };
+        let processed_file = ProcessedFile {
+            absolute_path: file_path.clone(),
+            ..Default::default()
+        };
+        cached_file("before").write(&empty_cache_entry, &processed_file)?;
+
+        assert!(matches!(
+            cached_file("before").get(&file_path)?,
+            CacheResult::Processed(_)
+        ));
+        assert!(matches!(
+            cached_file("after").get(&file_path)?,
+            CacheResult::Miss(_)
+        ));
+
+        fs::remove_dir_all(&cache_dir)?;
+        Ok(())
+    }
+
     #[test]
     fn test_corrupt_cache() -> anyhow::Result<()> {
         let sha = "e57a05216069923190a4e03d264d9677";
@@ -186,13 +288,14 @@ mod tests {
 
         let cache_path = PathBuf::from("tests/fixtures/simple_app/tmp/cache/");
         fs::create_dir_all(&cache_path).context("unable to create cache dir")?;
-        let corrupt_file_path = cache_path.join(format!("{}", sha));
+        let corrupt_file_path = cache_path.join(sha);
         fs::write(&corrupt_file_path, corrupt_contents)
             .context("expected to write corrupt cache file")?;
 
         let empty_cache_entry = EmptyCacheEntry::new(
             &cache_path,
             &PathBuf::from("tests/fixtures/simple_app/packs/foo/app/services/foo/bar.rb"),
+            "",
         )
         .context("expected tests/fixtures/simple_app/packs/foo/app/services/foo/bar.rb to exist")?;
 
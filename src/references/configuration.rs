@@ -1,23 +1,57 @@
 use std::{
     collections::{HashMap, HashSet},
     fmt,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use crate::references::{
     cache::{create_cache_dir_idempotently, Cache, NoopCache},
     cached_file::CachedFile,
+    error::ReferencesError,
+    file_walker::FileWalker,
+    inflections::Inflections,
+    packs,
+    packwerk_config::{PackwerkConfig, PACKWERK_YML},
+    reference::PositionBase,
 };
 
 pub struct Configuration {
     pub absolute_root: PathBuf,
     pub included_files: HashSet<PathBuf>,
-    pub acronyms: HashSet<String>,
+    // packwerk-style globs, relative to absolute_root, used to build included_files
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    // globs for the directories that may contain a package.yml
+    pub package_paths: Vec<String>,
+    // acronyms and plural/singular rules from config/initializers/inflections.rb
+    pub inflections: Inflections,
     // has pack.default_autoload_roots and pack.autoload_roots
     pub autoload_paths: HashMap<PathBuf, String>,
+    // Zeitwerk collapse and ignore globs, relative to absolute_root, e.g. "packs/*/app/components/*".
+    // A collapsed directory does not add a namespace; an ignored file or directory defines nothing.
+    pub zeitwerk_collapse: Vec<String>,
+    pub zeitwerk_ignore: Vec<String>,
     pub custom_associations: Vec<String>,
+    // Absolute paths of files that reopen a constant owned by another file, e.g. a pack
+    // patching `Widget`. References never resolve to these files.
+    pub ignored_monkeypatches: HashSet<PathBuf>,
     pub ruby_special_files: Vec<&'static str>,
     pub ruby_extensions: Vec<&'static str>,
+    // Also resolve constants to the classes, modules and constant assignments the parser
+    // finds, for files and names that Zeitwerk does not infer (lib/, nested constants)
+    pub index_parsed_definitions: bool,
+    // Fail with ReferencesError::ParseFailed when any included file has syntax errors,
+    // instead of reporting them as diagnostics next to the references
+    pub strict_parse: bool,
+    // Report string and symbol literals passed to `constantize`, `safe_constantize` and
+    // `const_get` as references of kind ReferenceKind::Dynamic
+    pub string_constant_references: bool,
+    // Leave out references of kind ReferenceKind::TypeOnly, i.e. constants only used in
+    // Sorbet signatures and type arguments
+    pub exclude_type_only_references: bool,
+    // Whether lines and columns of Reference::source_range count from 0 or from 1 (the default).
+    // Reference::source_location is unaffected and keeps packwerk's positions.
+    pub position_base: PositionBase,
     // Include references whose constants are defined in the same file
     pub include_reference_is_definition: bool,
     pub cache_enabled: bool,
@@ -26,6 +60,7 @@ pub struct Configuration {
 }
 
 pub trait ExtraReferenceFieldsFn: Sync + Send {
+    #[allow(clippy::ptr_arg)]
     fn extra_reference_fields_fn(
         &self,
         referencing_file_path: &PathBuf,
@@ -38,13 +73,30 @@ impl fmt::Debug for Configuration {
         f.debug_struct("Configuration")
             .field("absolute_root", &self.absolute_root)
             .field("included_files", &self.included_files)
-            .field("acronyms", &self.acronyms)
+            .field("include", &self.include)
+            .field("exclude", &self.exclude)
+            .field("package_paths", &self.package_paths)
+            .field("inflections", &self.inflections)
             .field("autoload_paths", &self.autoload_paths)
+            .field("zeitwerk_collapse", &self.zeitwerk_collapse)
+            .field("zeitwerk_ignore", &self.zeitwerk_ignore)
             .field("custom_associations", &self.custom_associations)
+            .field("ignored_monkeypatches", &self.ignored_monkeypatches)
             .field("ruby_special_files", &self.ruby_special_files)
             .field("ruby_extensions", &self.ruby_extensions)
             .field("cache_enabled", &self.cache_enabled)
             .field("cache_directory", &self.cache_directory)
+            .field("index_parsed_definitions", &self.index_parsed_definitions)
+            .field("strict_parse", &self.strict_parse)
+            .field(
+                "string_constant_references",
+                &self.string_constant_references,
+            )
+            .field(
+                "exclude_type_only_references",
+                &self.exclude_type_only_references,
+            )
+            .field("position_base", &self.position_base)
             .field(
                 "include_reference_is_definition",
                 &self.include_reference_is_definition,
@@ -59,11 +111,22 @@ impl Default for Configuration {
         Configuration {
             absolute_root: PathBuf::from(""),
             included_files: HashSet::new(),
-            acronyms: HashSet::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            package_paths: Vec::new(),
+            inflections: Inflections::default(),
             autoload_paths: HashMap::new(),
+            zeitwerk_collapse: Vec::new(),
+            zeitwerk_ignore: Vec::new(),
             custom_associations: Vec::new(),
+            ignored_monkeypatches: HashSet::new(),
             ruby_special_files: vec!["Gemfile", "Rakefile"],
             ruby_extensions: vec!["rb", "rake", "builder", "gemspec", "ru"],
+            index_parsed_definitions: false,
+            strict_parse: false,
+            string_constant_references: false,
+            exclude_type_only_references: false,
+            position_base: PositionBase::default(),
             include_reference_is_definition: false,
             cache_enabled: false,
             cache_directory: PathBuf::from("tmp/cache"),
@@ -73,18 +136,100 @@ impl Default for Configuration {
 }
 
 impl Configuration {
+    /// Builds a configuration from `<absolute_root>/packwerk.yml`, honoring `include`,
+    /// `exclude`, `package_paths`, `custom_associations`, `cache`, `cache_directory`
+    /// `autoload_roots`, `zeitwerk_collapse`, `zeitwerk_ignore`, `ignored_monkeypatches`,
+    /// `index_parsed_definitions`, `strict_parse`, `string_constant_references` and
+    /// `exclude_type_only_references`. Keys that are absent fall back to packwerk's defaults.
+    ///
+    /// Like packwerk's, the default `include` only matches `.rb`, `.rake` and `.erb` files.
+    /// HAML and Slim templates are parsed only when `include` lists them, e.g.
+    /// `"**/*.{rb,rake,erb,haml,slim}"`.
+    ///
+    /// Inflections are read from `config/initializers/inflections.rb` when it exists.
+    /// `autoload_paths` holds the default Zeitwerk roots of every discovered pack,
+    /// overridden by per-pack and then application-wide `autoload_roots`.
+    pub fn from_packwerk_yml(absolute_root: &Path) -> Result<Configuration, ReferencesError> {
+        let absolute_root =
+            absolute_root
+                .canonicalize()
+                .map_err(|source| ReferencesError::UnreadableFile {
+                    path: absolute_root.to_path_buf(),
+                    source,
+                })?;
+        let packwerk_config = PackwerkConfig::from_path(&absolute_root.join(PACKWERK_YML))?;
+
+        let packs = packs::discover_packs(
+            &absolute_root,
+            &packwerk_config.package_paths,
+            &packwerk_config.exclude,
+        )?;
+        let mut autoload_paths = packs::autoload_paths(&packs);
+        for (path, namespace) in &packwerk_config.autoload_roots {
+            autoload_paths.insert(absolute_root.join(path), namespace.to_owned());
+        }
+        let included_files = FileWalker::new(
+            &absolute_root,
+            &packwerk_config.include,
+            &packwerk_config.exclude,
+        )?
+        .walk();
+        let ignored_monkeypatches = packwerk_config
+            .ignored_monkeypatches
+            .iter()
+            .map(|path| absolute_root.join(path))
+            .collect();
+
+        Ok(Configuration {
+            included_files,
+            include: packwerk_config.include,
+            exclude: packwerk_config.exclude,
+            package_paths: packwerk_config.package_paths,
+            autoload_paths,
+            zeitwerk_collapse: packwerk_config.zeitwerk_collapse,
+            zeitwerk_ignore: packwerk_config.zeitwerk_ignore,
+            inflections: Inflections::load(&absolute_root)?,
+            custom_associations: packwerk_config.custom_associations,
+            ignored_monkeypatches,
+            index_parsed_definitions: packwerk_config.index_parsed_definitions,
+            strict_parse: packwerk_config.strict_parse,
+            string_constant_references: packwerk_config.string_constant_references,
+            exclude_type_only_references: packwerk_config.exclude_type_only_references,
+            cache_enabled: packwerk_config.cache,
+            cache_directory: absolute_root.join(packwerk_config.cache_directory),
+            absolute_root,
+            ..Default::default()
+        })
+    }
+
     pub(crate) fn get_cache(&self) -> Box<dyn Cache + Send + Sync> {
         if self.cache_enabled {
             let cache_dir = self.reference_cache_dir();
 
             let _ = create_cache_dir_idempotently(&cache_dir);
 
-            Box::new(CachedFile { cache_dir })
+            Box::new(CachedFile {
+                cache_dir,
+                settings_digest: self.cache_settings_digest(),
+            })
         } else {
             Box::new(NoopCache {})
         }
     }
 
+    // A digest of the settings process_file reads besides the file itself. Cache entries
+    // written with other settings, e.g. before an inflection was added, are misses.
+    pub(crate) fn cache_settings_digest(&self) -> String {
+        let settings = format!(
+            "{}:{:?}:{}",
+            self.inflections.digest(),
+            self.custom_associations,
+            self.include_reference_is_definition
+        );
+        format!("{:x}", md5::compute(settings))
+    }
+
+    #[cfg(test)]
     pub(crate) fn delete_cache(&self) -> anyhow::Result<()> {
         Ok(std::fs::remove_dir_all(&self.cache_directory)?)
     }
@@ -92,6 +237,18 @@ impl Configuration {
     pub(crate) fn reference_cache_dir(&self) -> PathBuf {
         self.cache_directory.join("ruby-references")
     }
+
+    // The path of a file relative to absolute_root, as it is reported to callers
+    pub(crate) fn relative_path(&self, absolute_path: &Path) -> Result<String, ReferencesError> {
+        Ok(absolute_path
+            .strip_prefix(&self.absolute_root)
+            .map_err(|_| ReferencesError::PathOutsideRoot {
+                path: absolute_path.to_path_buf(),
+                absolute_root: self.absolute_root.to_path_buf(),
+            })?
+            .to_string_lossy()
+            .to_string())
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -101,7 +258,7 @@ mod tests {
     fn default_configuration() {
         let configuration = Configuration::default();
         assert_eq!(configuration.absolute_root, PathBuf::from(""));
-        assert_eq!(configuration.acronyms, HashSet::new());
+        assert_eq!(configuration.inflections, Inflections::default());
         assert_eq!(configuration.autoload_paths, HashMap::new());
         assert_eq!(configuration.custom_associations, Vec::<String>::new());
         assert_eq!(
@@ -113,4 +270,112 @@ mod tests {
             vec!["rb", "rake", "builder", "gemspec", "ru"]
         );
     }
+
+    #[test]
+    fn cache_settings_digest() {
+        let digest = Configuration::default().cache_settings_digest();
+        assert_eq!(digest, Configuration::default().cache_settings_digest());
+
+        let mut configuration = Configuration::default();
+        configuration.inflections.acronym("API");
+        assert_ne!(digest, configuration.cache_settings_digest());
+
+        let configuration = Configuration {
+            custom_associations: vec![String::from("has_one_attached")],
+            ..Default::default()
+        };
+        assert_ne!(digest, configuration.cache_settings_digest());
+    }
+
+    #[test]
+    fn from_packwerk_yml() -> anyhow::Result<()> {
+        let configuration =
+            Configuration::from_packwerk_yml(Path::new("tests/fixtures/simple_app"))?;
+        let absolute_root = PathBuf::from("tests/fixtures/simple_app").canonicalize()?;
+
+        assert_eq!(configuration.absolute_root, absolute_root);
+        assert_eq!(
+            configuration.autoload_paths,
+            HashMap::from([
+                (
+                    absolute_root.join("app/company_data"),
+                    String::from("::Company")
+                ),
+                (absolute_root.join("app/services"), String::from("")),
+                (absolute_root.join("packs/bar/app/models"), String::from("")),
+                (
+                    absolute_root.join("packs/bar/app/models/concerns"),
+                    String::from("")
+                ),
+                (
+                    absolute_root.join("packs/bar/app/services"),
+                    String::from("")
+                ),
+                (
+                    absolute_root.join("packs/baz/app/services"),
+                    String::from("")
+                ),
+                (
+                    absolute_root.join("packs/foo/app/services"),
+                    String::from("")
+                ),
+                (absolute_root.join("packs/foo/app/views"), String::from("")),
+            ])
+        );
+        assert!(!configuration.cache_enabled);
+        assert_eq!(
+            configuration.cache_directory,
+            absolute_root.join("tmp/cache/packwerk")
+        );
+        assert_eq!(configuration.package_paths, vec!["**/"]);
+
+        let mut included_files = configuration
+            .included_files
+            .iter()
+            .map(|path| path.strip_prefix(&absolute_root).unwrap().to_owned())
+            .collect::<Vec<PathBuf>>();
+        included_files.sort();
+        // script/, tmp/ and node_modules/ are excluded by default
+        assert_eq!(
+            included_files,
+            vec![
+                PathBuf::from("app/company_data/widget.rb"),
+                PathBuf::from("app/services/some_root_class.rb"),
+                PathBuf::from("frontend/ui_helper.rb"),
+                PathBuf::from("packs/bar/app/models/concerns/some_concern.rb"),
+                PathBuf::from("packs/bar/app/services/bar.rb"),
+                PathBuf::from("packs/baz/app/services/baz.rb"),
+                PathBuf::from("packs/foo/app/services/foo/bar.rb"),
+                PathBuf::from("packs/foo/app/services/foo.rb"),
+                PathBuf::from("packs/foo/app/views/foo.erb"),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn from_packwerk_yml_missing_root() {
+        let error = Configuration::from_packwerk_yml(Path::new("tests/fixtures/does_not_exist"))
+            .unwrap_err();
+        assert!(matches!(error, ReferencesError::UnreadableFile { .. }));
+    }
+
+    #[test]
+    fn from_packwerk_yml_invalid_key() {
+        let error =
+            Configuration::from_packwerk_yml(Path::new("tests/fixtures/invalid_packwerk_config"))
+                .unwrap_err();
+        match &error {
+            ReferencesError::Config {
+                path: Some(path),
+                key: Some(key),
+                ..
+            } => {
+                assert!(path.ends_with("invalid_packwerk_config/packwerk.yml"));
+                assert_eq!(key, "custom_associations");
+            }
+            _ => panic!("expected a config error, got {:?}", error),
+        }
+        assert!(error.to_string().contains("`custom_associations`"));
+    }
 }
@@ -4,15 +4,9 @@ use std::{collections::HashMap, path::PathBuf};
 pub struct ConstantDefinition {
     pub fully_qualified_name: String,
     pub absolute_path_of_definition: PathBuf,
-}
-
-#[derive(Debug)]
-pub struct ConstantResolverConfiguration<'a> {
-    pub absolute_root: &'a PathBuf,
-    pub cache_directory: &'a PathBuf,
-    pub cache_enabled: bool,
-    pub inflections_path: &'a PathBuf,
-    pub autoload_roots: &'a HashMap<PathBuf, String>,
+    // A module Zeitwerk defines for a directory without a matching file. The definition
+    // path is that directory.
+    pub implicit_namespace: bool,
 }
 
 pub trait ConstantResolver {
@@ -25,4 +19,24 @@ pub trait ConstantResolver {
     fn fully_qualified_constant_name_to_constant_definition_map(
         &self,
     ) -> &HashMap<String, Vec<ConstantDefinition>>;
+
+    // The fully qualified name of the first definition `name` resolves to
+    fn resolve_name(&self, name: &str, namespace_path: &[String]) -> Option<String> {
+        let namespace_path = namespace_path
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<&str>>();
+        self.resolve(name, &namespace_path)?
+            .first()
+            .map(|definition| definition.fully_qualified_name.to_owned())
+    }
+}
+
+// `Foo::Bar` => `::Foo::Bar`, leaving names that already start with `::` alone
+pub(crate) fn root_qualified(name: &str) -> String {
+    if name.starts_with("::") {
+        name.to_owned()
+    } else {
+        format!("::{}", name)
+    }
 }
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+use crate::references::{parser::SourceLocation, reference::SourceRange};
+
+/// A class, module or constant assignment found by the parser
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Definition {
+    pub fully_qualified_name: String,
+    pub relative_defining_file: String,
+    // packwerk's position of the name: 1-based line, 0-based column
+    pub source_location: SourceLocation,
+    pub source_range: SourceRange,
+    // Whether Zeitwerk infers this constant from the path of the defining file. False for
+    // namespaces that merely wrap the inferred constant, nested constants and files
+    // outside of autoload paths.
+    pub inferred_by_zeitwerk: bool,
+}
@@ -0,0 +1,602 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::references::reference::Reference;
+
+// The number of references kept per edge to cut
+const MAX_SAMPLE_REFERENCES: usize = 3;
+
+// The largest component whose smallest set of edges to cut is searched for exactly. The
+// search takes time and memory in 2^size; larger components fall back to a heuristic.
+const MAX_EXACT_COMPONENT_SIZE: usize = 16;
+
+/// What the nodes of a `DependencyGraph` are
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum GroupBy {
+    // relative_referencing_file depends on relative_defining_file
+    File,
+    // The value of one extra field of the referencing file depends on the value of another
+    // of the defining file, e.g. "referencing_pack_name" and "defining_pack_name"
+    ExtraFields {
+        referencing: String,
+        defining: String,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct DependencyEdge {
+    pub from: String,
+    pub to: String,
+    pub reference_count: usize,
+    // the first references that create the edge, sorted
+    pub sample_references: Vec<Reference>,
+}
+
+/// A strongly connected component of the graph: every node in it depends on every other,
+/// directly or not
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct DependencyCycle {
+    // sorted
+    pub nodes: Vec<String>,
+    // The fewest edges whose removal leaves the component without any cycle, sorted. Above
+    // MAX_EXACT_COMPONENT_SIZE nodes, the set comes from the greedy heuristic of Eades, Lin
+    // and Smyth instead, which keeps it small though not always the smallest.
+    pub edges_to_cut: Vec<DependencyEdge>,
+}
+
+/// Dependencies between files or groups of files, built from references. References without
+/// a defining file, or without the extra fields grouped by, are left out, as are references
+/// within a single node.
+pub struct DependencyGraph<'a> {
+    // sorted, so that results do not depend on the order of the references
+    nodes: Vec<String>,
+    // node => sorted nodes it depends on
+    adjacency: Vec<Vec<usize>>,
+    edge_references: HashMap<(usize, usize), Vec<&'a Reference>>,
+}
+
+impl<'a> DependencyGraph<'a> {
+    pub fn new(references: &'a [Reference], group_by: &GroupBy) -> DependencyGraph<'a> {
+        let mut edges: BTreeMap<(&str, &str), Vec<&Reference>> = BTreeMap::new();
+        for reference in references {
+            let Some((from, to)) = endpoints(reference, group_by) else {
+                continue;
+            };
+            if from != to {
+                edges.entry((from, to)).or_default().push(reference);
+            }
+        }
+
+        let nodes = edges
+            .keys()
+            .flat_map(|(from, to)| [*from, *to])
+            .collect::<BTreeSet<&str>>()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<String>>();
+        let positions = nodes
+            .iter()
+            .enumerate()
+            .map(|(position, node)| (node.as_str(), position))
+            .collect::<HashMap<&str, usize>>();
+
+        let mut adjacency = vec![vec![]; nodes.len()];
+        let mut edge_references = HashMap::new();
+        for ((from, to), mut references) in edges {
+            let (from, to) = (positions[from], positions[to]);
+            // edges are visited in order, so each adjacency list ends up sorted
+            adjacency[from].push(to);
+            references.sort();
+            edge_references.insert((from, to), references);
+        }
+
+        DependencyGraph {
+            nodes,
+            adjacency,
+            edge_references,
+        }
+    }
+
+    /// The nodes `node` directly depends on, sorted
+    pub fn dependencies(&self, node: &str) -> Vec<&str> {
+        self.position(node)
+            .map(|position| {
+                self.adjacency[position]
+                    .iter()
+                    .map(|dependency| self.nodes[*dependency].as_str())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Every strongly connected component with more than one node, sorted by its nodes
+    pub fn cycles(&self) -> Vec<DependencyCycle> {
+        let mut cycles = self
+            .strongly_connected_components()
+            .into_iter()
+            .filter(|component| component.len() > 1)
+            .map(|mut component| {
+                component.sort();
+                let edges_to_cut = self
+                    .feedback_edges(&component)
+                    .into_iter()
+                    .map(|(from, to)| self.edge(from, to))
+                    .collect();
+                DependencyCycle {
+                    nodes: component
+                        .iter()
+                        .map(|node| self.nodes[*node].to_owned())
+                        .collect(),
+                    edges_to_cut,
+                }
+            })
+            .collect::<Vec<DependencyCycle>>();
+        cycles.sort_by(|a, b| a.nodes.cmp(&b.nodes));
+        cycles
+    }
+
+    fn position(&self, node: &str) -> Option<usize> {
+        self.nodes
+            .binary_search_by(|candidate| candidate.as_str().cmp(node))
+            .ok()
+    }
+
+    fn edge(&self, from: usize, to: usize) -> DependencyEdge {
+        let references = &self.edge_references[&(from, to)];
+        DependencyEdge {
+            from: self.nodes[from].to_owned(),
+            to: self.nodes[to].to_owned(),
+            reference_count: references.len(),
+            sample_references: references
+                .iter()
+                .take(MAX_SAMPLE_REFERENCES)
+                .map(|reference| (*reference).clone())
+                .collect(),
+        }
+    }
+
+    // Tarjan's algorithm, with an explicit stack so that long dependency chains cannot
+    // overflow the call stack
+    fn strongly_connected_components(&self) -> Vec<Vec<usize>> {
+        let node_count = self.nodes.len();
+        let mut next_index = 0;
+        let mut indexes: Vec<Option<usize>> = vec![None; node_count];
+        let mut lowlinks = vec![0; node_count];
+        let mut on_stack = vec![false; node_count];
+        let mut stack = vec![];
+        let mut components = vec![];
+
+        for start in 0..node_count {
+            if indexes[start].is_some() {
+                continue;
+            }
+            // (node, position of the next dependency to visit)
+            let mut call_stack = vec![(start, 0)];
+            indexes[start] = Some(next_index);
+            lowlinks[start] = next_index;
+            next_index += 1;
+            stack.push(start);
+            on_stack[start] = true;
+
+            while let Some(frame) = call_stack.last_mut() {
+                let node = frame.0;
+                if let Some(&dependency) = self.adjacency[node].get(frame.1) {
+                    frame.1 += 1;
+                    match indexes[dependency] {
+                        None => {
+                            indexes[dependency] = Some(next_index);
+                            lowlinks[dependency] = next_index;
+                            next_index += 1;
+                            stack.push(dependency);
+                            on_stack[dependency] = true;
+                            call_stack.push((dependency, 0));
+                        }
+                        Some(index) if on_stack[dependency] => {
+                            lowlinks[node] = lowlinks[node].min(index);
+                        }
+                        Some(_) => {}
+                    }
+                    continue;
+                }
+
+                call_stack.pop();
+                if let Some(&(caller, _)) = call_stack.last() {
+                    lowlinks[caller] = lowlinks[caller].min(lowlinks[node]);
+                }
+                if Some(lowlinks[node]) == indexes[node] {
+                    let mut component = vec![];
+                    while let Some(member) = stack.pop() {
+                        on_stack[member] = false;
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    components.push(component);
+                }
+            }
+        }
+        components
+    }
+
+    fn feedback_edges(&self, component: &[usize]) -> Vec<(usize, usize)> {
+        if component.len() <= MAX_EXACT_COMPONENT_SIZE {
+            self.minimum_feedback_edges(component)
+        } else {
+            self.greedy_feedback_edges(component)
+        }
+    }
+
+    // Edges within the sorted `component` that point backwards in an order of its nodes
+    // with the fewest such edges. The order is built by dynamic programming over the sets
+    // of nodes placed first: placing a node after a set costs its edges into the set. Ties
+    // go to the order found first.
+    fn minimum_feedback_edges(&self, component: &[usize]) -> Vec<(usize, usize)> {
+        let size = component.len();
+        let dependency_masks = component
+            .iter()
+            .map(|&node| {
+                self.adjacency[node]
+                    .iter()
+                    .filter_map(|dependency| component.binary_search(dependency).ok())
+                    .fold(0u32, |mask, position| mask | 1 << position)
+            })
+            .collect::<Vec<u32>>();
+
+        let set_count = 1usize << size;
+        let mut costs = vec![u32::MAX; set_count];
+        let mut last_placed = vec![0; set_count];
+        costs[0] = 0;
+        for set in 0..set_count {
+            for (position, dependency_mask) in dependency_masks.iter().enumerate() {
+                let node_bit = 1 << position;
+                if set & node_bit != 0 {
+                    continue;
+                }
+                let cost = costs[set] + (dependency_mask & set as u32).count_ones();
+                if cost < costs[set | node_bit] {
+                    costs[set | node_bit] = cost;
+                    last_placed[set | node_bit] = position;
+                }
+            }
+        }
+
+        let mut order = vec![0; size];
+        let mut set = set_count - 1;
+        for place in (0..size).rev() {
+            order[last_placed[set]] = place;
+            set &= !(1 << last_placed[set]);
+        }
+
+        let mut feedback_edges = vec![];
+        for (from, dependency_mask) in dependency_masks.iter().enumerate() {
+            for to in 0..size {
+                if dependency_mask & 1 << to != 0 && order[from] > order[to] {
+                    feedback_edges.push((component[from], component[to]));
+                }
+            }
+        }
+        feedback_edges
+    }
+
+    // Edges within the sorted `component` that point backwards in the order built by
+    // Eades, Lin and Smyth's heuristic: sinks go to the end, sources to the start, and
+    // otherwise the node with the most outgoing and fewest incoming edges goes next. Cut
+    // edges that can be kept without closing a cycle are then put back. Ties go to the
+    // first node.
+    fn greedy_feedback_edges(&self, component: &[usize]) -> Vec<(usize, usize)> {
+        let mut dependencies: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut dependents: HashMap<usize, Vec<usize>> = HashMap::new();
+        for &node in component {
+            for &dependency in &self.adjacency[node] {
+                if component.binary_search(&dependency).is_ok() {
+                    dependencies.entry(node).or_default().push(dependency);
+                    dependents.entry(dependency).or_default().push(node);
+                }
+            }
+        }
+        let neighbours = |edges: &HashMap<usize, Vec<usize>>, node: usize| {
+            edges.get(&node).cloned().unwrap_or_default()
+        };
+        let mut out_degrees: HashMap<usize, isize> = component
+            .iter()
+            .map(|&node| (node, neighbours(&dependencies, node).len() as isize))
+            .collect();
+        let mut in_degrees: HashMap<usize, isize> = component
+            .iter()
+            .map(|&node| (node, neighbours(&dependents, node).len() as isize))
+            .collect();
+
+        let mut remaining = component.iter().copied().collect::<BTreeSet<usize>>();
+        let (mut head, mut tail) = (vec![], vec![]);
+        while !remaining.is_empty() {
+            let node = if let Some(&sink) = remaining.iter().find(|node| out_degrees[node] == 0) {
+                tail.push(sink);
+                sink
+            } else if let Some(&source) = remaining.iter().find(|node| in_degrees[node] == 0) {
+                head.push(source);
+                source
+            } else {
+                let delta = |node: &usize| out_degrees[node] - in_degrees[node];
+                let mut next = *remaining.first().unwrap();
+                for node in &remaining {
+                    if delta(node) > delta(&next) {
+                        next = *node;
+                    }
+                }
+                head.push(next);
+                next
+            };
+            remaining.remove(&node);
+            for dependency in neighbours(&dependencies, node) {
+                *in_degrees.get_mut(&dependency).unwrap() -= 1;
+            }
+            for dependent in neighbours(&dependents, node) {
+                *out_degrees.get_mut(&dependent).unwrap() -= 1;
+            }
+        }
+        let positions = head
+            .into_iter()
+            .chain(tail.into_iter().rev())
+            .enumerate()
+            .map(|(position, node)| (node, position))
+            .collect::<HashMap<usize, usize>>();
+
+        let mut kept: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut cut = vec![];
+        for (&from, others) in &dependencies {
+            for &to in others {
+                if positions[&from] < positions[&to] {
+                    kept.entry(from).or_default().push(to);
+                } else {
+                    cut.push((from, to));
+                }
+            }
+        }
+        cut.sort();
+
+        let mut feedback_edges = vec![];
+        for (from, to) in cut {
+            // Keeping from -> to closes a cycle only if `to` already reaches `from`
+            if reaches(&kept, to, from) {
+                feedback_edges.push((from, to));
+            } else {
+                kept.entry(from).or_default().push(to);
+            }
+        }
+        feedback_edges
+    }
+}
+
+fn reaches(adjacency: &HashMap<usize, Vec<usize>>, from: usize, to: usize) -> bool {
+    let mut visited = HashSet::from([from]);
+    let mut stack = vec![from];
+    while let Some(node) = stack.pop() {
+        if node == to {
+            return true;
+        }
+        for &next in adjacency.get(&node).into_iter().flatten() {
+            if visited.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+    false
+}
+
+fn endpoints<'r>(reference: &'r Reference, group_by: &GroupBy) -> Option<(&'r str, &'r str)> {
+    match group_by {
+        GroupBy::File => Some((
+            reference.relative_referencing_file.as_str(),
+            reference.relative_defining_file.as_deref()?,
+        )),
+        GroupBy::ExtraFields {
+            referencing,
+            defining,
+        } => Some((
+            reference.extra_fields.get(referencing)?.as_str(),
+            reference.extra_fields.get(defining)?.as_str(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::references::{all_references, common_test::common_test::configuration_for_fixture};
+    use pretty_assertions::assert_eq;
+
+    fn edges_to_cut(cycle: &DependencyCycle) -> Vec<(&str, &str, usize)> {
+        cycle
+            .edges_to_cut
+            .iter()
+            .map(|edge| (edge.from.as_str(), edge.to.as_str(), edge.reference_count))
+            .collect()
+    }
+
+    // The references left once those behind the edges to cut are removed
+    fn without_edges_to_cut(
+        references: &[Reference],
+        cycles: &[DependencyCycle],
+        group_by: &GroupBy,
+    ) -> Vec<Reference> {
+        let cut = cycles
+            .iter()
+            .flat_map(|cycle| &cycle.edges_to_cut)
+            .map(|edge| (edge.from.as_str(), edge.to.as_str()))
+            .collect::<HashSet<(&str, &str)>>();
+        references
+            .iter()
+            .filter(|reference| {
+                endpoints(reference, group_by).is_none_or(|endpoints| !cut.contains(&endpoints))
+            })
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn file_cycles() -> anyhow::Result<()> {
+        let references = all_references(&configuration_for_fixture(
+            "tests/fixtures/app_with_cycles",
+            false,
+        ))?;
+        let graph = DependencyGraph::new(&references, &GroupBy::File);
+
+        assert_eq!(
+            graph.dependencies("packs/orders/app/models/order.rb"),
+            vec![
+                "packs/billing/app/models/invoice.rb",
+                "packs/customers/app/models/customer.rb"
+            ]
+        );
+
+        let cycles = graph.cycles();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(
+            cycles[0].nodes,
+            vec![
+                "packs/billing/app/models/invoice.rb",
+                "packs/customers/app/models/customer.rb",
+                "packs/orders/app/models/order.rb"
+            ]
+        );
+        // order.rb -> customer.rb -> order.rb and order.rb -> invoice.rb -> customer.rb ->
+        // order.rb share a single edge
+        assert_eq!(
+            edges_to_cut(&cycles[0]),
+            vec![(
+                "packs/customers/app/models/customer.rb",
+                "packs/orders/app/models/order.rb",
+                1
+            )]
+        );
+        let sample = &cycles[0].edges_to_cut[0].sample_references[0];
+        assert_eq!(sample.constant_name, "::Order");
+        assert_eq!(sample.source_location.line, 3);
+
+        let remaining = without_edges_to_cut(&references, &cycles, &GroupBy::File);
+        assert!(DependencyGraph::new(&remaining, &GroupBy::File)
+            .cycles()
+            .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn pack_cycles() -> anyhow::Result<()> {
+        let references = all_references(&configuration_for_fixture(
+            "tests/fixtures/app_with_cycles",
+            false,
+        ))?;
+        let group_by = GroupBy::ExtraFields {
+            referencing: String::from("referencing_pack_name"),
+            defining: String::from("defining_pack_name"),
+        };
+        let graph = DependencyGraph::new(&references, &group_by);
+
+        let cycles = graph.cycles();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(
+            cycles[0].nodes,
+            vec!["packs/billing", "packs/customers", "packs/orders"]
+        );
+        assert_eq!(
+            edges_to_cut(&cycles[0]),
+            vec![("packs/customers", "packs/orders", 1)]
+        );
+        assert!(graph.dependencies("packs/unknown").is_empty());
+
+        let remaining = without_edges_to_cut(&references, &cycles, &group_by);
+        assert!(DependencyGraph::new(&remaining, &group_by)
+            .cycles()
+            .is_empty());
+        Ok(())
+    }
+
+    // A reference from <from>.rb to the constant defined in <to>.rb
+    fn reference(from: &str, to: &str) -> Reference {
+        Reference {
+            constant_name: format!("::{}", to.to_uppercase()),
+            relative_defining_file: Some(format!("{}.rb", to)),
+            relative_referencing_file: format!("{}.rb", from),
+            source_location: Default::default(),
+            source_range: Default::default(),
+            extra_fields: HashMap::new(),
+            implicit_namespace: false,
+            kind: Default::default(),
+        }
+    }
+
+    fn assert_acyclic_without_edges_to_cut(references: &[Reference], cycles: &[DependencyCycle]) {
+        let remaining = without_edges_to_cut(references, cycles, &GroupBy::File);
+        assert!(DependencyGraph::new(&remaining, &GroupBy::File)
+            .cycles()
+            .is_empty());
+    }
+
+    #[test]
+    fn every_cycle_of_a_dense_component_is_cut() {
+        let nodes = ["a", "b", "c", "d"];
+        let references = nodes
+            .iter()
+            .flat_map(|from| nodes.iter().map(move |to| reference(from, to)))
+            .chain([reference("d", "e"), reference("e", "d")])
+            .collect::<Vec<Reference>>();
+
+        let cycles = DependencyGraph::new(&references, &GroupBy::File).cycles();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(
+            cycles[0].nodes,
+            vec!["a.rb", "b.rb", "c.rb", "d.rb", "e.rb"]
+        );
+        // One of the two edges between each pair of a, b, c and d, and one between d and e
+        assert_eq!(cycles[0].edges_to_cut.len(), 7);
+
+        assert_acyclic_without_edges_to_cut(&references, &cycles);
+    }
+
+    #[test]
+    fn fewer_edges_than_the_greedy_heuristic() {
+        let references = [
+            ("a", "b"),
+            ("b", "d"),
+            ("c", "a"),
+            ("c", "b"),
+            ("d", "a"),
+            ("d", "c"),
+        ]
+        .into_iter()
+        .map(|(from, to)| reference(from, to))
+        .collect::<Vec<Reference>>();
+        let graph = DependencyGraph::new(&references, &GroupBy::File);
+
+        // The heuristic cuts d -> a and d -> c, though b -> d alone is in every cycle
+        assert_eq!(
+            graph.greedy_feedback_edges(&[0, 1, 2, 3]),
+            vec![(3, 0), (3, 2)]
+        );
+
+        let cycles = graph.cycles();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(edges_to_cut(&cycles[0]), vec![("b.rb", "d.rb", 1)]);
+        assert_acyclic_without_edges_to_cut(&references, &cycles);
+    }
+
+    #[test]
+    fn components_above_the_exact_size_fall_back_to_the_heuristic() {
+        let nodes = (0..MAX_EXACT_COMPONENT_SIZE + 4)
+            .map(|node| format!("node_{:02}", node))
+            .collect::<Vec<String>>();
+        let references = (0..nodes.len())
+            .flat_map(|node| {
+                [1, 2].map(|step| reference(&nodes[node], &nodes[(node + step) % nodes.len()]))
+            })
+            .collect::<Vec<Reference>>();
+
+        let cycles = DependencyGraph::new(&references, &GroupBy::File).cycles();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].nodes.len(), nodes.len());
+        assert!(!cycles[0].edges_to_cut.is_empty());
+        assert_acyclic_without_edges_to_cut(&references, &cycles);
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    error, fmt, io,
+    path::{Path, PathBuf},
+};
+
+use crate::references::parser::ParseDiagnostic;
+
+/// The error type of this crate's public API. Each variant carries the path it concerns,
+/// so that callers can decide per variant whether to skip a file, retry or abort.
+#[derive(Debug)]
+pub enum ReferencesError {
+    // A source or configuration file could not be read
+    UnreadableFile {
+        path: PathBuf,
+        source: io::Error,
+    },
+    // A file is not within `absolute_root`, so no relative path can be reported for it
+    PathOutsideRoot {
+        path: PathBuf,
+        absolute_root: PathBuf,
+    },
+    // Reading or writing the cache directory failed
+    CacheIo {
+        path: PathBuf,
+        source: io::Error,
+    },
+    // A cache file exists but could not be decoded
+    CorruptCache {
+        path: PathBuf,
+        message: String,
+    },
+    // packwerk.yml, a package.yml or a `Configuration` value is invalid.
+    // `path` is None for values set in code rather than read from a file.
+    Config {
+        path: Option<PathBuf>,
+        key: Option<String>,
+        message: String,
+    },
+    // `strict_parse` is set and at least one included file has syntax errors.
+    // Holds the error diagnostics of every such file, sorted by file and position.
+    ParseFailed {
+        diagnostics: Vec<ParseDiagnostic>,
+    },
+    // A failure none of the other variants describe, e.g. a broken internal invariant
+    Internal {
+        context: String,
+    },
+}
+
+impl ReferencesError {
+    pub(crate) fn invalid_key(path: Option<&Path>, key: &str, message: impl fmt::Display) -> Self {
+        ReferencesError::Config {
+            path: path.map(Path::to_path_buf),
+            key: Some(key.to_owned()),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ReferencesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReferencesError::UnreadableFile { path, source } => {
+                write!(f, "Failed to read {}: {}", path.display(), source)
+            }
+            ReferencesError::PathOutsideRoot {
+                path,
+                absolute_root,
+            } => write!(
+                f,
+                "{} is not within absolute_root {}",
+                path.display(),
+                absolute_root.display()
+            ),
+            ReferencesError::CacheIo { path, source } => {
+                write!(f, "Cache I/O failed for {}: {}", path.display(), source)
+            }
+            ReferencesError::CorruptCache { path, message } => {
+                write!(f, "Corrupt cache file {}: {}", path.display(), message)
+            }
+            ReferencesError::Config { path, key, message } => match (path, key) {
+                (Some(path), Some(key)) => write!(
+                    f,
+                    "invalid value for `{}` in {}: {}",
+                    key,
+                    path.display(),
+                    message
+                ),
+                (Some(path), None) => write!(f, "invalid {}: {}", path.display(), message),
+                (None, Some(key)) => write!(f, "invalid value for `{}`: {}", key, message),
+                (None, None) => write!(f, "invalid configuration: {}", message),
+            },
+            ReferencesError::ParseFailed { diagnostics } => {
+                write!(f, "Failed to parse:")?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n{}", diagnostic)?;
+                }
+                Ok(())
+            }
+            ReferencesError::Internal { context } => write!(f, "{}", context),
+        }
+    }
+}
+
+impl error::Error for ReferencesError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ReferencesError::UnreadableFile { source, .. }
+            | ReferencesError::CacheIo { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// Internally errors are `anyhow::Error`s, many of which wrap a `ReferencesError` under
+// added context. At the public API boundary the wrapped error is recovered.
+impl From<anyhow::Error> for ReferencesError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<ReferencesError>() {
+            Ok(error) => error,
+            Err(error) => ReferencesError::Internal {
+                context: format!("{:#}", error),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context;
+
+    use super::*;
+
+    #[test]
+    fn recovers_error_under_context() {
+        let result: anyhow::Result<()> = Err(ReferencesError::PathOutsideRoot {
+            path: PathBuf::from("/elsewhere/foo.rb"),
+            absolute_root: PathBuf::from("/app"),
+        }
+        .into());
+        let error = ReferencesError::from(result.context("resolving references").unwrap_err());
+
+        assert!(matches!(
+            &error,
+            ReferencesError::PathOutsideRoot { path, .. } if path == &PathBuf::from("/elsewhere/foo.rb")
+        ));
+        assert_eq!(
+            error.to_string(),
+            "/elsewhere/foo.rb is not within absolute_root /app"
+        );
+    }
+
+    #[test]
+    fn other_errors_are_internal() {
+        let error = ReferencesError::from(anyhow::anyhow!("expecting configuration"));
+        assert!(matches!(error, ReferencesError::Internal { .. }));
+        assert_eq!(error.to_string(), "expecting configuration");
+    }
+}
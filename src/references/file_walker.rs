@@ -0,0 +1,185 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use globset::GlobSet;
+use ignore::WalkBuilder;
+use tracing::warn;
+
+use crate::references::{error::ReferencesError, packwerk_config::build_glob_set};
+
+// Directories at the root of the application that are never worth descending into.
+// Directories with the same names deeper in the tree, e.g. app/models/vendor, are walked.
+pub const DEFAULT_SKIPPED_DIRECTORIES: [&str; 3] = ["node_modules", "tmp", "vendor"];
+
+/// Enumerates the files of an application in a single walk of `absolute_root`.
+///
+/// A file is included when its path relative to `absolute_root` matches one of the
+/// `include` globs and none of the `exclude` globs. Directories directly under
+/// `absolute_root` named in `skipped_directories` are pruned without being read.
+pub struct FileWalker {
+    absolute_root: PathBuf,
+    include: GlobSet,
+    exclude: GlobSet,
+    skipped_directories: Vec<String>,
+    respect_gitignore: bool,
+}
+
+impl FileWalker {
+    pub fn new(
+        absolute_root: &Path,
+        include: &[String],
+        exclude: &[String],
+    ) -> Result<FileWalker, ReferencesError> {
+        Ok(FileWalker {
+            absolute_root: absolute_root.to_path_buf(),
+            include: build_glob_set(include)
+                .map_err(|e| ReferencesError::invalid_key(None, "include", format!("{:#}", e)))?,
+            exclude: build_glob_set(exclude)
+                .map_err(|e| ReferencesError::invalid_key(None, "exclude", format!("{:#}", e)))?,
+            skipped_directories: DEFAULT_SKIPPED_DIRECTORIES
+                .iter()
+                .map(|dir| dir.to_string())
+                .collect(),
+            respect_gitignore: false,
+        })
+    }
+
+    pub fn skipped_directories(mut self, skipped_directories: Vec<String>) -> Self {
+        self.skipped_directories = skipped_directories;
+        self
+    }
+
+    // When enabled, files matched by a .gitignore within absolute_root are skipped
+    pub fn respect_gitignore(mut self, respect_gitignore: bool) -> Self {
+        self.respect_gitignore = respect_gitignore;
+        self
+    }
+
+    // Entries that cannot be read are skipped with a warning rather than failing the walk
+    pub fn walk(&self) -> HashSet<PathBuf> {
+        let skipped_directories = self.skipped_directories.clone();
+        WalkBuilder::new(&self.absolute_root)
+            .standard_filters(false)
+            .git_ignore(self.respect_gitignore)
+            .require_git(false)
+            .filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                !(is_dir
+                    && entry.depth() == 1
+                    && skipped_directories
+                        .iter()
+                        .any(|skipped| entry.file_name() == skipped.as_str()))
+            })
+            .build()
+            .filter_map(|entry| match entry {
+                Ok(entry) => Some(entry),
+                // e.g. an unreadable directory, whose files are then missing from the walk
+                Err(e) => {
+                    warn!(
+                        "Skipping an entry while walking {:?}: {}",
+                        self.absolute_root, e
+                    );
+                    None
+                }
+            })
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .filter_map(|entry| {
+                let relative_path = entry.path().strip_prefix(&self.absolute_root).ok()?;
+                if self.include.is_match(relative_path) && !self.exclude.is_match(relative_path) {
+                    Some(entry.into_path())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::references::common_test::common_test::get_absolute_root;
+    use pretty_assertions::assert_eq;
+
+    const APP: &str = "tests/fixtures/app_with_ignored_files";
+
+    fn relative_walk(walker: &FileWalker) -> Vec<String> {
+        let absolute_root = get_absolute_root(APP);
+        let mut files = walker
+            .walk()
+            .iter()
+            .map(|path| {
+                path.strip_prefix(&absolute_root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect::<Vec<String>>();
+        files.sort();
+        files
+    }
+
+    fn walker(exclude: &[&str]) -> FileWalker {
+        let exclude = exclude.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        FileWalker::new(
+            &get_absolute_root(APP),
+            &[String::from("**/*.{rb,rake,erb}")],
+            &exclude,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn includes_erb_and_skips_default_directories() {
+        assert_eq!(
+            relative_walk(&walker(&[])),
+            vec![
+                "app/models/user.rb",
+                "app/views/users/show.html.erb",
+                "generated/schema.rb",
+                "packs/foo/app/models/foo.rb",
+                // only the root vendor directory is skipped
+                "packs/foo/app/models/vendor/api_client.rb",
+                "packs/foo/spec/foo_spec.rb",
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_excludes() {
+        assert_eq!(
+            relative_walk(&walker(&["packs/*/spec/**/*"])),
+            vec![
+                "app/models/user.rb",
+                "app/views/users/show.html.erb",
+                "generated/schema.rb",
+                "packs/foo/app/models/foo.rb",
+                "packs/foo/app/models/vendor/api_client.rb",
+            ]
+        );
+    }
+
+    #[test]
+    fn respects_gitignore() {
+        assert_eq!(
+            relative_walk(&walker(&[]).respect_gitignore(true)),
+            vec![
+                "app/models/user.rb",
+                "app/views/users/show.html.erb",
+                "packs/foo/app/models/foo.rb",
+                "packs/foo/app/models/vendor/api_client.rb",
+                "packs/foo/spec/foo_spec.rb",
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_skipped_directories() {
+        let files = relative_walk(&walker(&[]).skipped_directories(vec![]));
+        assert!(files.contains(&String::from("node_modules/pkg/index.rb")));
+        assert!(files.contains(&String::from("tmp/cache.rb")));
+        assert!(files.contains(&String::from("vendor/gems/gem.rb")));
+    }
+}
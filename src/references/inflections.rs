@@ -0,0 +1,820 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use lib_ruby_parser::{nodes, traverse::visitor::Visitor, Node, Parser, ParserOptions};
+use regex::{Regex, RegexBuilder};
+use tracing::warn;
+
+use crate::references::error::ReferencesError;
+
+pub const INFLECTIONS_RB: &str = "config/initializers/inflections.rb";
+
+#[derive(Debug, Clone)]
+pub struct InflectionRule {
+    pub pattern: Regex,
+    // In the syntax of `Regex::replace`, e.g. "${1}en"
+    pub replacement: String,
+}
+
+impl PartialEq for InflectionRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern.as_str() == other.pattern.as_str() && self.replacement == other.replacement
+    }
+}
+
+impl Eq for InflectionRule {}
+
+// ActiveSupport's default English rules, in definition order.
+// See https://github.com/rails/rails/blob/main/activesupport/lib/active_support/inflections.rb
+const PLURALS: [(&str, &str); 21] = [
+    ("$", "s"),
+    ("(?i)s$", "s"),
+    ("(?i)^(ax|test)is$", "${1}es"),
+    ("(?i)(octop|vir)us$", "${1}i"),
+    ("(?i)(octop|vir)i$", "${1}i"),
+    ("(?i)(alias|status)$", "${1}es"),
+    ("(?i)(bu)s$", "${1}ses"),
+    ("(?i)(buffal|tomat)o$", "${1}oes"),
+    ("(?i)([ti])um$", "${1}a"),
+    ("(?i)([ti])a$", "${1}a"),
+    ("(?i)sis$", "ses"),
+    ("(?i)(?:([^f])fe|([lr])f)$", "${1}${2}ves"),
+    ("(?i)(hive)$", "${1}s"),
+    ("(?i)([^aeiouy]|qu)y$", "${1}ies"),
+    ("(?i)(x|ch|ss|sh)$", "${1}es"),
+    ("(?i)(matr|vert|ind)(?:ix|ex)$", "${1}ices"),
+    ("(?i)^(m|l)ouse$", "${1}ice"),
+    ("(?i)^(m|l)ice$", "${1}ice"),
+    ("(?i)^(ox)$", "${1}en"),
+    ("(?i)^(oxen)$", "${1}"),
+    ("(?i)(quiz)$", "${1}zes"),
+];
+
+const SINGULARS: [(&str, &str); 27] = [
+    ("(?i)s$", ""),
+    ("(?i)(ss)$", "${1}"),
+    ("(?i)(n)ews$", "${1}ews"),
+    ("(?i)([ti])a$", "${1}um"),
+    (
+        "(?i)((a)naly|(b)a|(d)iagno|(p)arenthe|(p)rogno|(s)ynop|(t)he)(sis|ses)$",
+        "${1}sis",
+    ),
+    ("(?i)(^analy)(sis|ses)$", "${1}sis"),
+    ("(?i)([^f])ves$", "${1}fe"),
+    ("(?i)(hive)s$", "${1}"),
+    ("(?i)(tive)s$", "${1}"),
+    ("(?i)([lr])ves$", "${1}f"),
+    ("(?i)([^aeiouy]|qu)ies$", "${1}y"),
+    ("(?i)(s)eries$", "${1}eries"),
+    ("(?i)(m)ovies$", "${1}ovie"),
+    ("(?i)(x|ch|ss|sh)es$", "${1}"),
+    ("(?i)^(m|l)ice$", "${1}ouse"),
+    ("(?i)(bus)(es)?$", "${1}"),
+    ("(?i)(o)es$", "${1}"),
+    ("(?i)(shoe)s$", "${1}"),
+    ("(?i)(cris|test)(is|es)$", "${1}is"),
+    ("(?i)^(a)x[ie]s$", "${1}xis"),
+    ("(?i)(octop|vir)(us|i)$", "${1}us"),
+    ("(?i)(alias|status)(es)?$", "${1}"),
+    ("(?i)^(ox)en", "${1}"),
+    ("(?i)(vert|ind)ices$", "${1}ex"),
+    ("(?i)(matr)ices$", "${1}ix"),
+    ("(?i)(quiz)zes$", "${1}"),
+    ("(?i)(database)s$", "${1}"),
+];
+
+const IRREGULARS: [(&str, &str); 6] = [
+    ("person", "people"),
+    ("man", "men"),
+    ("child", "children"),
+    ("sex", "sexes"),
+    ("move", "moves"),
+    ("zombie", "zombies"),
+];
+
+const UNCOUNTABLES: [&str; 10] = [
+    "equipment",
+    "information",
+    "rice",
+    "money",
+    "species",
+    "series",
+    "fish",
+    "sheep",
+    "jeans",
+    "police",
+];
+
+/// A port of `ActiveSupport::Inflector::Inflections` for the `:en` locale.
+///
+/// `Inflections::default()` holds ActiveSupport's default rules, and the application's
+/// `config/initializers/inflections.rb` extends them the same way it does in Rails.
+/// Rules are kept with the highest precedence first, so a rule defined later wins
+/// over earlier ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inflections {
+    // Downcased acronym => acronym, e.g. "api" => "API"
+    pub acronyms: HashMap<String, String>,
+    pub plurals: Vec<InflectionRule>,
+    pub singulars: Vec<InflectionRule>,
+    // (singular, plural)
+    pub irregulars: Vec<(String, String)>,
+    pub uncountables: HashSet<String>,
+}
+
+impl Default for Inflections {
+    fn default() -> Self {
+        static ENGLISH: OnceLock<Inflections> = OnceLock::new();
+        ENGLISH.get_or_init(Inflections::english).clone()
+    }
+}
+
+impl Inflections {
+    fn english() -> Inflections {
+        let mut inflections = Inflections {
+            acronyms: HashMap::new(),
+            plurals: vec![],
+            singulars: vec![],
+            irregulars: vec![],
+            uncountables: HashSet::new(),
+        };
+        let rule = |(pattern, replacement): &(&str, &str)| InflectionRule {
+            pattern: Regex::new(pattern).expect("default inflection rules are valid"),
+            replacement: replacement.to_string(),
+        };
+        for plural in PLURALS.iter() {
+            inflections.plural(rule(plural));
+        }
+        for singular in SINGULARS.iter() {
+            inflections.singular(rule(singular));
+        }
+        for (singular, plural) in IRREGULARS {
+            inflections.irregular(singular, plural);
+        }
+        for uncountable in UNCOUNTABLES {
+            inflections.uncountable(uncountable);
+        }
+        inflections
+    }
+
+    /// Loads `config/initializers/inflections.rb` under `absolute_root`, if it exists.
+    pub fn load(absolute_root: &Path) -> Result<Inflections, ReferencesError> {
+        let path = absolute_root.join(INFLECTIONS_RB);
+        if path.exists() {
+            Inflections::from_path(&path)
+        } else {
+            Ok(Inflections::default())
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Result<Inflections, ReferencesError> {
+        let contents =
+            fs::read_to_string(path).map_err(|source| ReferencesError::UnreadableFile {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        Ok(Inflections::from_contents(&contents, path))
+    }
+
+    fn from_contents(contents: &str, path: &Path) -> Inflections {
+        let options = ParserOptions {
+            buffer_name: path.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let mut collector = InflectionsCollector {
+            inflections: Inflections::default(),
+            block_variables: vec![],
+            path: path.to_path_buf(),
+        };
+        if let Some(ast) = Parser::new(contents, options).do_parse().ast {
+            collector.visit(&ast);
+        }
+        collector.inflections
+    }
+
+    pub fn acronym(&mut self, word: &str) {
+        self.acronyms.insert(word.to_lowercase(), word.to_owned());
+    }
+
+    pub fn plural(&mut self, rule: InflectionRule) {
+        self.uncountables.remove(&rule.replacement);
+        self.plurals.insert(0, rule);
+    }
+
+    pub fn singular(&mut self, rule: InflectionRule) {
+        self.uncountables.remove(&rule.replacement);
+        self.singulars.insert(0, rule);
+    }
+
+    pub fn irregular(&mut self, singular: &str, plural: &str) {
+        self.uncountables.remove(singular);
+        self.uncountables.remove(plural);
+        self.irregulars
+            .push((singular.to_owned(), plural.to_owned()));
+
+        let (Some(s0), Some(p0)) = (singular.chars().next(), plural.chars().next()) else {
+            return;
+        };
+        let (singular_rest, plural_rest) = (&singular[s0.len_utf8()..], &plural[p0.len_utf8()..]);
+        let (s_rest, p_rest) = (regex::escape(singular_rest), regex::escape(plural_rest));
+        let (singular_rest, plural_rest) = (
+            escape_replacement(singular_rest),
+            escape_replacement(plural_rest),
+        );
+        let rule = |pattern: String, replacement: String| InflectionRule {
+            pattern: Regex::new(&pattern).expect("irregular patterns are escaped"),
+            replacement,
+        };
+
+        if s0.to_uppercase().eq(p0.to_uppercase()) {
+            let s0 = regex::escape(&s0.to_string());
+            let p0 = regex::escape(&p0.to_string());
+            self.plural(rule(
+                format!("(?i)({}){}$", s0, s_rest),
+                format!("${{1}}{}", plural_rest),
+            ));
+            self.plural(rule(
+                format!("(?i)({}){}$", p0, p_rest),
+                format!("${{1}}{}", plural_rest),
+            ));
+            self.singular(rule(
+                format!("(?i)({}){}$", s0, s_rest),
+                format!("${{1}}{}", singular_rest),
+            ));
+            self.singular(rule(
+                format!("(?i)({}){}$", p0, p_rest),
+                format!("${{1}}{}", singular_rest),
+            ));
+        } else {
+            let (s_up, s_down) = (s0.to_uppercase().to_string(), s0.to_lowercase().to_string());
+            let (p_up, p_down) = (p0.to_uppercase().to_string(), p0.to_lowercase().to_string());
+            let pattern =
+                |first: &str, rest: &str| format!("{}(?i){}$", regex::escape(first), rest);
+            let replacement =
+                |first: &str, rest: &str| format!("{}{}", escape_replacement(first), rest);
+            self.plural(rule(
+                pattern(&s_up, &s_rest),
+                replacement(&p_up, &plural_rest),
+            ));
+            self.plural(rule(
+                pattern(&s_down, &s_rest),
+                replacement(&p_down, &plural_rest),
+            ));
+            self.plural(rule(
+                pattern(&p_up, &p_rest),
+                replacement(&p_up, &plural_rest),
+            ));
+            self.plural(rule(
+                pattern(&p_down, &p_rest),
+                replacement(&p_down, &plural_rest),
+            ));
+            self.singular(rule(
+                pattern(&s_up, &s_rest),
+                replacement(&s_up, &singular_rest),
+            ));
+            self.singular(rule(
+                pattern(&s_down, &s_rest),
+                replacement(&s_down, &singular_rest),
+            ));
+            self.singular(rule(
+                pattern(&p_up, &p_rest),
+                replacement(&s_up, &singular_rest),
+            ));
+            self.singular(rule(
+                pattern(&p_down, &p_rest),
+                replacement(&s_down, &singular_rest),
+            ));
+        }
+    }
+
+    // Stable across runs, unlike the iteration order of the sets
+    pub(crate) fn digest(&self) -> String {
+        let mut acronyms = self.acronyms.values().collect::<Vec<&String>>();
+        acronyms.sort();
+        let mut uncountables = self.uncountables.iter().collect::<Vec<&String>>();
+        uncountables.sort();
+        fn rules(rules: &[InflectionRule]) -> Vec<(&str, &str)> {
+            rules
+                .iter()
+                .map(|rule| (rule.pattern.as_str(), rule.replacement.as_str()))
+                .collect()
+        }
+        let inflections = format!(
+            "{:?}",
+            (
+                acronyms,
+                rules(&self.plurals),
+                rules(&self.singulars),
+                &self.irregulars,
+                uncountables
+            )
+        );
+        format!("{:x}", md5::compute(inflections))
+    }
+
+    pub fn uncountable(&mut self, word: &str) {
+        self.uncountables.insert(word.to_lowercase());
+    }
+
+    // A word is uncountable when it ends with an uncountable word at a word boundary, e.g.
+    // "fish" and "big fish" but not "selfish" or "big_fish".
+    // See ActiveSupport::Inflector::Inflections::Uncountables#uncountable?
+    pub fn is_uncountable(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        self.uncountables.iter().any(|uncountable| {
+            word.strip_suffix(uncountable.as_str())
+                .is_some_and(|prefix| !prefix.ends_with(|c: char| c.is_alphanumeric() || c == '_'))
+        })
+    }
+
+    pub fn singularize(&self, word: &str) -> String {
+        self.apply_inflections(word, &self.singulars)
+    }
+
+    pub fn pluralize(&self, word: &str) -> String {
+        self.apply_inflections(word, &self.plurals)
+    }
+
+    // See ActiveSupport::Inflector#apply_inflections: the first matching rule wins
+    fn apply_inflections(&self, word: &str, rules: &[InflectionRule]) -> String {
+        if word.is_empty() || self.is_uncountable(word) {
+            return word.to_owned();
+        }
+        match rules.iter().find(|rule| rule.pattern.is_match(word)) {
+            Some(rule) => rule
+                .pattern
+                .replace(word, rule.replacement.as_str())
+                .to_string(),
+            None => word.to_owned(),
+        }
+    }
+}
+
+struct InflectionsCollector {
+    inflections: Inflections,
+    // Names of the variables yielded by `ActiveSupport::Inflector.inflections do |inflect|`
+    block_variables: Vec<String>,
+    path: PathBuf,
+}
+
+impl Visitor for InflectionsCollector {
+    fn on_block(&mut self, node: &nodes::Block) {
+        let block_variable = match (&*node.call, node.args.as_deref()) {
+            (Node::Send(call), Some(Node::Args(args))) if is_english_inflections_call(call) => {
+                args.args.first().and_then(argument_name)
+            }
+            _ => None,
+        };
+
+        match block_variable {
+            Some(block_variable) => {
+                self.block_variables.push(block_variable);
+                if let Some(body) = &node.body {
+                    self.visit(body);
+                }
+                self.block_variables.pop();
+            }
+            None => {
+                // Blocks for other locales are skipped entirely
+                if let Node::Send(call) = &*node.call {
+                    if call.method_name == "inflections" {
+                        return;
+                    }
+                }
+                lib_ruby_parser::traverse::visitor::visit_block(self, node)
+            }
+        }
+    }
+
+    fn on_send(&mut self, node: &nodes::Send) {
+        let receives_inflections = match node.recv.as_deref() {
+            Some(Node::Lvar(lvar)) => self.block_variables.contains(&lvar.name),
+            // ActiveSupport::Inflector.inflections(:en).acronym("API")
+            Some(Node::Send(call)) => is_english_inflections_call(call),
+            _ => false,
+        };
+
+        if receives_inflections {
+            self.apply(node);
+        }
+
+        lib_ruby_parser::traverse::visitor::visit_send(self, node);
+    }
+}
+
+impl InflectionsCollector {
+    fn apply(&mut self, node: &nodes::Send) {
+        match node.method_name.as_str() {
+            "acronym" => {
+                if let Some(word) = node.args.first().and_then(string_value) {
+                    self.inflections.acronym(&word);
+                }
+            }
+            "plural" | "singular" => {
+                let rule = match (node.args.first(), node.args.get(1).and_then(string_value)) {
+                    (Some(rule), Some(replacement)) => self.rule(rule, &replacement),
+                    _ => None,
+                };
+                match (rule, node.method_name.as_str()) {
+                    (Some(rule), "plural") => self.inflections.plural(rule),
+                    (Some(rule), _) => self.inflections.singular(rule),
+                    (None, _) => {}
+                }
+            }
+            "irregular" => {
+                if let (Some(singular), Some(plural)) = (
+                    node.args.first().and_then(string_value),
+                    node.args.get(1).and_then(string_value),
+                ) {
+                    self.inflections.irregular(&singular, &plural);
+                }
+            }
+            "uncountable" => {
+                for word in node.args.iter().flat_map(string_values) {
+                    self.inflections.uncountable(&word);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn rule(&self, rule: &Node, replacement: &str) -> Option<InflectionRule> {
+        let pattern = match rule {
+            Node::Regexp(regexp) => {
+                let source = regexp
+                    .parts
+                    .iter()
+                    .map(string_value)
+                    .collect::<Option<String>>()?;
+                let options = match regexp.options.as_deref() {
+                    Some(Node::RegOpt(reg_opt)) => reg_opt.options.clone().unwrap_or_default(),
+                    _ => String::new(),
+                };
+                ruby_regex(&source, &options)
+            }
+            // String rules match literally, like String#sub
+            Node::Str(_) => Regex::new(&regex::escape(&string_value(rule)?)).ok(),
+            _ => None,
+        };
+
+        match pattern {
+            Some(pattern) => Some(InflectionRule {
+                pattern,
+                replacement: ruby_replacement(replacement),
+            }),
+            None => {
+                warn!(
+                    "Skipping unsupported inflection rule in {}",
+                    self.path.display()
+                );
+                None
+            }
+        }
+    }
+}
+
+fn is_english_inflections_call(call: &nodes::Send) -> bool {
+    if call.method_name != "inflections" {
+        return false;
+    }
+    match call.args.first() {
+        None => true,
+        Some(locale) => string_value(locale).is_some_and(|locale| locale == "en"),
+    }
+}
+
+fn argument_name(node: &Node) -> Option<String> {
+    match node {
+        Node::Arg(arg) => Some(arg.name.to_owned()),
+        Node::Procarg0(procarg) => procarg.args.first().and_then(argument_name),
+        _ => None,
+    }
+}
+
+fn string_value(node: &Node) -> Option<String> {
+    match node {
+        Node::Str(s) => Some(s.value.to_string_lossy()),
+        Node::Sym(s) => Some(s.name.to_string_lossy()),
+        _ => None,
+    }
+}
+
+// Flattens `uncountable "fish", %w(sheep deer)` into its words
+fn string_values(node: &Node) -> Vec<String> {
+    match node {
+        Node::Array(array) => array.elements.iter().flat_map(string_values).collect(),
+        node => string_value(node).into_iter().collect(),
+    }
+}
+
+// Translates the Ruby regexp syntax used in inflection rules to the regex crate's syntax
+fn ruby_regex(source: &str, options: &str) -> Option<Regex> {
+    let source = source.replace("\\Z", "\\z").replace("\\h", "[0-9a-fA-F]");
+    RegexBuilder::new(&source)
+        .case_insensitive(options.contains('i'))
+        .dot_matches_new_line(options.contains('m'))
+        .ignore_whitespace(options.contains('x'))
+        .build()
+        .ok()
+}
+
+// Irregular words are inserted literally, so `$` must not start a group reference
+fn escape_replacement(literal: &str) -> String {
+    literal.replace('$', "$$")
+}
+
+// Ruby replacements refer to groups as \1, the regex crate as ${1}
+fn ruby_replacement(replacement: &str) -> String {
+    let mut result = String::new();
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(digit)) if digit.is_ascii_digit() => {
+                result.push_str(&format!("${{{}}}", digit));
+                chars.next();
+            }
+            ('$', _) => result.push_str("$$"),
+            (c, _) => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn parse(contents: &str) -> Inflections {
+        Inflections::from_contents(contents, Path::new("inflections.rb"))
+    }
+
+    // From ActiveSupport's test/inflector_test_cases.rb
+    const SINGULAR_TO_PLURAL: [(&str, &str); 87] = [
+        ("search", "searches"),
+        ("switch", "switches"),
+        ("fix", "fixes"),
+        ("box", "boxes"),
+        ("process", "processes"),
+        ("address", "addresses"),
+        ("case", "cases"),
+        ("stack", "stacks"),
+        ("wish", "wishes"),
+        ("fish", "fish"),
+        ("jeans", "jeans"),
+        ("funky jeans", "funky jeans"),
+        ("my money", "my money"),
+        ("category", "categories"),
+        ("query", "queries"),
+        ("ability", "abilities"),
+        ("agency", "agencies"),
+        ("movie", "movies"),
+        ("archive", "archives"),
+        ("index", "indices"),
+        ("wife", "wives"),
+        ("safe", "saves"),
+        ("half", "halves"),
+        ("move", "moves"),
+        ("salesperson", "salespeople"),
+        ("person", "people"),
+        ("spokesman", "spokesmen"),
+        ("man", "men"),
+        ("woman", "women"),
+        ("basis", "bases"),
+        ("diagnosis", "diagnoses"),
+        ("diagnosis_a", "diagnosis_as"),
+        ("datum", "data"),
+        ("medium", "media"),
+        ("stadium", "stadia"),
+        ("analysis", "analyses"),
+        ("my_analysis", "my_analyses"),
+        ("node_child", "node_children"),
+        ("child", "children"),
+        ("experience", "experiences"),
+        ("day", "days"),
+        ("comment", "comments"),
+        ("foobar", "foobars"),
+        ("newsletter", "newsletters"),
+        ("old_news", "old_news"),
+        ("news", "news"),
+        ("series", "series"),
+        ("species", "species"),
+        ("quiz", "quizzes"),
+        ("perspective", "perspectives"),
+        ("ox", "oxen"),
+        ("photo", "photos"),
+        ("buffalo", "buffaloes"),
+        ("tomato", "tomatoes"),
+        ("dwarf", "dwarves"),
+        ("elf", "elves"),
+        ("information", "information"),
+        ("equipment", "equipment"),
+        ("bus", "buses"),
+        ("status", "statuses"),
+        ("status_code", "status_codes"),
+        ("mouse", "mice"),
+        ("louse", "lice"),
+        ("house", "houses"),
+        ("octopus", "octopi"),
+        ("virus", "viri"),
+        ("alias", "aliases"),
+        ("portfolio", "portfolios"),
+        ("vertex", "vertices"),
+        ("matrix", "matrices"),
+        ("matrix_fu", "matrix_fus"),
+        ("axis", "axes"),
+        ("taxi", "taxis"),
+        ("testis", "testes"),
+        ("crisis", "crises"),
+        ("rice", "rice"),
+        ("shoe", "shoes"),
+        ("horse", "horses"),
+        ("prize", "prizes"),
+        ("edge", "edges"),
+        ("database", "databases"),
+        ("|ice", "|ices"),
+        ("|ouse", "|ouses"),
+        ("slice", "slices"),
+        ("police", "police"),
+        ("zombie", "zombies"),
+        ("sex", "sexes"),
+    ];
+
+    // Ruby's String#capitalize
+    fn capitalize(word: &str) -> String {
+        let mut chars = word.chars();
+        match chars.next() {
+            None => String::new(),
+            Some(first) => {
+                first.to_uppercase().collect::<String>() + &chars.as_str().to_lowercase()
+            }
+        }
+    }
+
+    #[test]
+    fn pluralize_singular() {
+        let inflections = Inflections::default();
+        for (singular, plural) in SINGULAR_TO_PLURAL {
+            assert_eq!(plural, inflections.pluralize(singular));
+            assert_eq!(
+                capitalize(plural),
+                inflections.pluralize(&capitalize(singular))
+            );
+        }
+    }
+
+    #[test]
+    fn singularize_plural() {
+        let inflections = Inflections::default();
+        for (singular, plural) in SINGULAR_TO_PLURAL {
+            assert_eq!(singular, inflections.singularize(plural));
+            assert_eq!(
+                capitalize(singular),
+                inflections.singularize(&capitalize(plural))
+            );
+        }
+    }
+
+    #[test]
+    fn pluralize_plural() {
+        let inflections = Inflections::default();
+        for (_, plural) in SINGULAR_TO_PLURAL {
+            assert_eq!(plural, inflections.pluralize(plural));
+            assert_eq!(
+                capitalize(plural),
+                inflections.pluralize(&capitalize(plural))
+            );
+        }
+    }
+
+    #[test]
+    fn singularize_singular() {
+        let inflections = Inflections::default();
+        for (singular, _) in SINGULAR_TO_PLURAL {
+            assert_eq!(singular, inflections.singularize(singular));
+            assert_eq!(
+                capitalize(singular),
+                inflections.singularize(&capitalize(singular))
+            );
+        }
+    }
+
+    #[test]
+    fn fixture_acronyms() -> anyhow::Result<()> {
+        let inflections = Inflections::load(Path::new("tests/fixtures/app_with_inflections"))?;
+        assert_eq!(
+            inflections.acronyms,
+            HashMap::from([
+                (String::from("api"), String::from("API")),
+                (String::from("csv"), String::from("CSV"))
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn missing_file_uses_defaults() -> anyhow::Result<()> {
+        let inflections = Inflections::load(Path::new("tests/fixtures/simple_app"))?;
+        assert_eq!(inflections, Inflections::default());
+        Ok(())
+    }
+
+    #[test]
+    fn commented_out_rules_are_ignored() -> anyhow::Result<()> {
+        let inflections = Inflections::load(Path::new("tests/fixtures/small-app"))?;
+        assert_eq!(inflections, Inflections::default());
+        Ok(())
+    }
+
+    #[test]
+    fn all_rule_kinds() {
+        let inflections = parse(
+            r#"
+ActiveSupport::Inflector.inflections(:en) do |inflect|
+  inflect.plural /^(ax)$/i, "\\1en"
+  inflect.singular /^(ax)en/i, '\1'
+  inflect.irregular(
+    "cactus",
+    "cacti"
+  )
+  inflect.uncountable %w( staff deer )
+  inflect.uncountable "feedback", :aircraft
+  inflect.acronym "RESTful"
+end
+"#,
+        );
+
+        assert_eq!(
+            inflections.acronyms,
+            HashMap::from([(String::from("restful"), String::from("RESTful"))])
+        );
+        assert!(inflections
+            .irregulars
+            .contains(&(String::from("cactus"), String::from("cacti"))));
+        for uncountable in ["staff", "deer", "feedback", "aircraft"] {
+            assert!(inflections.uncountables.contains(uncountable));
+        }
+        assert_eq!(inflections.pluralize("ax"), "axen");
+        assert_eq!(inflections.singularize("axen"), "ax");
+        assert_eq!(inflections.singularize("cacti"), "cactus");
+        assert_eq!(inflections.singularize("big_cacti"), "big_cactus");
+        assert_eq!(inflections.pluralize("Cactus"), "Cacti");
+        assert_eq!(inflections.pluralize("deer"), "deer");
+        assert!(inflections.is_uncountable("Red Deer"));
+        assert!(!inflections.is_uncountable("red_deer"));
+        assert_eq!(inflections.singularize("companies"), "company");
+    }
+
+    #[test]
+    fn other_locales_are_ignored() {
+        let inflections = parse(
+            r#"
+ActiveSupport::Inflector.inflections(:es) do |inflect|
+  inflect.irregular "el", "los"
+end
+
+ActiveSupport::Inflector.inflections do |i|
+  i.uncountable "news"
+end
+
+ActiveSupport::Inflector.inflections(:en).acronym("HTML")
+"#,
+        );
+        assert_eq!(inflections.irregulars, Inflections::default().irregulars);
+        assert!(inflections.uncountables.contains("news"));
+        assert_eq!(
+            inflections.acronyms,
+            HashMap::from([(String::from("html"), String::from("HTML"))])
+        );
+    }
+
+    #[test]
+    fn irregular_with_punctuation() {
+        let mut inflections = Inflections::default();
+        inflections.irregular("t-shirt", "t-shirts");
+        inflections.irregular("u.s. dollar", "u.s. dollar$");
+        inflections.irregular("$ign", "$igns");
+        assert_eq!(inflections.pluralize("t-shirt"), "t-shirts");
+        assert_eq!(inflections.singularize("T-shirts"), "T-shirt");
+        assert_eq!(inflections.pluralize("u.s. dollar"), "u.s. dollar$");
+        assert_eq!(inflections.singularize("u.s. dollar$"), "u.s. dollar");
+        assert_eq!(inflections.pluralize("$ign"), "$igns");
+        assert_eq!(inflections.singularize("$igns"), "$ign");
+
+        inflections.irregular("buck", "$bucks");
+        assert_eq!(inflections.pluralize("buck"), "$bucks");
+        assert_eq!(inflections.singularize("$bucks"), "buck");
+    }
+
+    #[test]
+    fn irregular_with_different_first_letters() {
+        let mut inflections = Inflections::default();
+        inflections.irregular("zombie", "brains");
+        assert_eq!(inflections.singularize("brains"), "zombie");
+        assert_eq!(inflections.pluralize("Zombie"), "Brains");
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::references::{
+    configuration::Configuration,
+    constant_resolver::root_qualified,
+    error::ReferencesError,
+    parser::{parse, SourceLocation},
+    zeitwerk::get_zeitwerk_constant_resolver,
+};
+
+/// A `class Foo < Bar` declaration. `superclass_name` is fully qualified when it resolves,
+/// and root-qualified as written otherwise, e.g. `::ActiveRecord::Base` from a gem.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Inheritance {
+    pub class_name: String,
+    pub superclass_name: String,
+    pub relative_file: String,
+    pub source_location: SourceLocation,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct InheritanceGraph {
+    // sorted by file and position. A class reopened with its superclass repeated appears
+    // once per declaration.
+    pub inheritances: Vec<Inheritance>,
+}
+
+impl InheritanceGraph {
+    /// The superclass of the first declaration of `class_name` that names one
+    pub fn superclass(&self, class_name: &str) -> Option<&str> {
+        let class_name = root_qualified(class_name);
+        self.inheritances
+            .iter()
+            .find(|inheritance| inheritance.class_name == class_name)
+            .map(|inheritance| inheritance.superclass_name.as_str())
+    }
+
+    /// The classes that directly inherit from `class_name`, sorted
+    pub fn subclasses(&self, class_name: &str) -> Vec<&str> {
+        let class_name = root_qualified(class_name);
+        let mut subclasses = self
+            .inheritances
+            .iter()
+            .filter(|inheritance| inheritance.superclass_name == class_name)
+            .map(|inheritance| inheritance.class_name.as_str())
+            .collect::<Vec<&str>>();
+        subclasses.sort();
+        subclasses.dedup();
+        subclasses
+    }
+
+    /// Every class that inherits from `class_name`, directly or not, sorted
+    pub fn descendants(&self, class_name: &str) -> Vec<&str> {
+        let mut descendants = vec![];
+        let mut seen = HashSet::new();
+        let mut pending = self.subclasses(class_name);
+        while let Some(subclass) = pending.pop() {
+            if seen.insert(subclass) {
+                descendants.push(subclass);
+                pending.extend(self.subclasses(subclass));
+            }
+        }
+        descendants.sort();
+        descendants
+    }
+
+    /// The superclasses of `class_name`, nearest first. The chain ends at the first class
+    /// without a known superclass.
+    pub fn ancestors(&self, class_name: &str) -> Vec<&str> {
+        let mut ancestors: Vec<&str> = vec![];
+        let mut current = root_qualified(class_name);
+        while let Some(superclass) = self.superclass(&current) {
+            // Guards against a class that inherits from one of its own descendants
+            if ancestors.contains(&superclass) {
+                break;
+            }
+            ancestors.push(superclass);
+            current = root_qualified(superclass);
+        }
+        ancestors
+    }
+
+    /// The last of `ancestors`, or `class_name` itself when it has no known superclass
+    pub fn root_ancestor(&self, class_name: &str) -> String {
+        self.ancestors(class_name)
+            .last()
+            .map(|ancestor| ancestor.to_string())
+            .unwrap_or_else(|| root_qualified(class_name))
+    }
+}
+
+/// Every `class Foo < Bar` in the included files whose superclass is a constant, with the
+/// superclasses resolved like references.
+pub fn inheritance_graph(
+    configuration: &Configuration,
+) -> Result<InheritanceGraph, ReferencesError> {
+    let processed_files = parse(configuration)?;
+    let constant_resolver = get_zeitwerk_constant_resolver(configuration, &processed_files)?;
+
+    let mut inheritances = vec![];
+    for processed_file in &processed_files {
+        let relative_file = configuration.relative_path(&processed_file.absolute_path)?;
+        for superclass in &processed_file.superclasses {
+            let superclass_name = constant_resolver
+                .resolve_name(&superclass.superclass_name, &superclass.namespace_path)
+                .unwrap_or_else(|| root_qualified(&superclass.superclass_name));
+            inheritances.push(Inheritance {
+                class_name: superclass.class_name.to_owned(),
+                superclass_name,
+                relative_file: relative_file.to_owned(),
+                source_location: SourceLocation {
+                    line: superclass.location.start_row,
+                    column: superclass.location.start_col,
+                },
+            });
+        }
+    }
+    inheritances.sort_by(|a, b| {
+        (&a.relative_file, &a.source_location).cmp(&(&b.relative_file, &b.source_location))
+    });
+
+    Ok(InheritanceGraph { inheritances })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::references::common_test::common_test::configuration_for_fixture;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn descendants_and_ancestors() -> anyhow::Result<()> {
+        let configuration = configuration_for_fixture("tests/fixtures/app_with_inheritance", false);
+        let graph = inheritance_graph(&configuration)?;
+
+        assert_eq!(
+            graph.superclass("Billing::Refund"),
+            Some("::Billing::Invoice")
+        );
+        assert_eq!(
+            graph.subclasses("ApplicationRecord"),
+            vec!["::Billing::Invoice", "::User"]
+        );
+        assert_eq!(
+            graph.descendants("::ApplicationRecord"),
+            vec![
+                "::Admin",
+                "::Billing::Invoice",
+                "::Billing::Refund",
+                "::User"
+            ]
+        );
+        assert!(graph.descendants("Admin").is_empty());
+        assert_eq!(
+            graph.ancestors("Billing::Refund"),
+            vec![
+                "::Billing::Invoice",
+                "::ApplicationRecord",
+                "::ActiveRecord::Base"
+            ]
+        );
+        assert_eq!(graph.root_ancestor("Admin"), "::ActiveRecord::Base");
+        assert_eq!(
+            graph.subclasses("::ActiveRecord::Base"),
+            vec!["::ApplicationRecord"]
+        );
+        assert_eq!(
+            graph.descendants("ActiveRecord::Base"),
+            vec![
+                "::Admin",
+                "::ApplicationRecord",
+                "::Billing::Invoice",
+                "::Billing::Refund",
+                "::User"
+            ]
+        );
+        assert_eq!(graph.root_ancestor("Unknown"), "::Unknown");
+
+        let admin = graph
+            .inheritances
+            .iter()
+            .find(|inheritance| inheritance.class_name == "::Admin")
+            .unwrap();
+        assert_eq!(admin.relative_file, "app/models/admin.rb");
+        assert_eq!(admin.source_location, SourceLocation { line: 1, column: 6 });
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::references::{
+    configuration::Configuration,
+    constant_resolver::root_qualified,
+    error::ReferencesError,
+    parser::{parse, MixinKind, SourceLocation},
+    zeitwerk::get_zeitwerk_constant_resolver,
+};
+
+/// A module mixed into a class or module with `include`, `extend` or `prepend`.
+/// `module_name` is fully qualified when it resolves, and root-qualified as written
+/// otherwise, e.g. `::Comparable` from the standard library.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Mixin {
+    pub class_name: String,
+    pub module_name: String,
+    pub kind: MixinKind,
+    pub relative_file: String,
+    pub source_location: SourceLocation,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Mixins {
+    // sorted by file and position, which is declaration order within a file
+    pub mixins: Vec<Mixin>,
+}
+
+impl Mixins {
+    /// The mixins declared in the body of `class_name` itself, in declaration order
+    pub fn of(&self, class_name: &str) -> Vec<&Mixin> {
+        let class_name = root_qualified(class_name);
+        self.mixins
+            .iter()
+            .filter(|mixin| mixin.class_name == class_name)
+            .collect()
+    }
+
+    /// What `class_name.ancestors` lists up to the class itself, leaving out superclasses:
+    /// prepended modules, the class, then included modules, each expanded with the modules
+    /// it prepends and includes in turn.
+    pub fn ancestors(&self, class_name: &str) -> Vec<String> {
+        self.module_ancestors(&root_qualified(class_name), &mut HashSet::new())
+    }
+
+    /// The modules `extend` adds to the singleton class of `class_name`, most recent first,
+    /// each expanded like `ancestors`
+    pub fn extended_modules(&self, class_name: &str) -> Vec<String> {
+        let mut extended_modules = vec![];
+        for mixin in self.of(class_name) {
+            if mixin.kind == MixinKind::Extend {
+                let chain = self.module_ancestors(&mixin.module_name, &mut HashSet::new());
+                add_to_front(&mut extended_modules, chain, &[]);
+            }
+        }
+        extended_modules
+    }
+
+    // Replays the mixins of `module_name` in declaration order, like Ruby: each one puts
+    // its own ancestors in front of the modules mixed in before it, leaving out those that
+    // are already in the chain.
+    fn module_ancestors(&self, module_name: &str, visiting: &mut HashSet<String>) -> Vec<String> {
+        // Guards against modules that include each other
+        if !visiting.insert(module_name.to_owned()) {
+            return vec![];
+        }
+
+        let (mut prepended, mut included) = (vec![], vec![]);
+        for mixin in self.of(module_name) {
+            let chain = self.module_ancestors(&mixin.module_name, visiting);
+            match mixin.kind {
+                MixinKind::Prepend => {
+                    let in_chain = [included.as_slice(), &[module_name.to_owned()]].concat();
+                    add_to_front(&mut prepended, chain, &in_chain);
+                }
+                MixinKind::Include => {
+                    let in_chain = [prepended.as_slice(), &[module_name.to_owned()]].concat();
+                    add_to_front(&mut included, chain, &in_chain);
+                }
+                MixinKind::Extend => {}
+            }
+        }
+
+        visiting.remove(module_name);
+        prepended
+            .into_iter()
+            .chain([module_name.to_owned()])
+            .chain(included)
+            .collect()
+    }
+}
+
+// Puts the modules of `chain` that are neither in `modules` nor in `in_chain` at the front
+// of `modules`, in order
+fn add_to_front(modules: &mut Vec<String>, chain: Vec<String>, in_chain: &[String]) {
+    let added = chain
+        .into_iter()
+        .filter(|module| !modules.contains(module) && !in_chain.contains(module))
+        .collect::<Vec<String>>();
+    modules.splice(0..0, added);
+}
+
+/// Every `include`, `extend` and `prepend` of a constant in the included files, with the
+/// mixed in modules resolved like references.
+pub fn all_mixins(configuration: &Configuration) -> Result<Mixins, ReferencesError> {
+    let processed_files = parse(configuration)?;
+    let constant_resolver = get_zeitwerk_constant_resolver(configuration, &processed_files)?;
+
+    let mut mixins = vec![];
+    for processed_file in &processed_files {
+        let relative_file = configuration.relative_path(&processed_file.absolute_path)?;
+        for mixin in &processed_file.mixins {
+            let module_name = constant_resolver
+                .resolve_name(&mixin.module_name, &mixin.namespace_path)
+                .unwrap_or_else(|| root_qualified(&mixin.module_name));
+            mixins.push(Mixin {
+                class_name: mixin.class_name.to_owned(),
+                module_name,
+                kind: mixin.kind,
+                relative_file: relative_file.to_owned(),
+                source_location: SourceLocation {
+                    line: mixin.location.start_row,
+                    column: mixin.location.start_col,
+                },
+            });
+        }
+    }
+    mixins.sort_by(|a, b| {
+        (&a.relative_file, &a.source_location).cmp(&(&b.relative_file, &b.source_location))
+    });
+
+    Ok(Mixins { mixins })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::references::common_test::common_test::configuration_for_fixture;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn mixins_and_ancestors() -> anyhow::Result<()> {
+        let configuration = configuration_for_fixture("tests/fixtures/app_with_mixins", false);
+        let mixins = all_mixins(&configuration)?;
+
+        let order_mixins = mixins
+            .of("::Order")
+            .iter()
+            .map(|mixin| {
+                (
+                    mixin.module_name.as_str(),
+                    mixin.kind,
+                    mixin.source_location.line,
+                )
+            })
+            .collect::<Vec<(&str, MixinKind, usize)>>();
+        assert_eq!(
+            order_mixins,
+            vec![
+                ("::Auditable", MixinKind::Include, 2),
+                ("::Billing::Payable", MixinKind::Include, 3),
+                ("::Comparable", MixinKind::Include, 4),
+                ("::Tracing", MixinKind::Prepend, 5),
+                ("::Searchable", MixinKind::Extend, 6),
+            ]
+        );
+        assert_eq!(mixins.of("Order"), mixins.of("::Order"));
+        assert_eq!(mixins.of("::Order")[0].relative_file, "app/models/order.rb");
+
+        // Like Ruby, Loggable stays where Auditable, included first, put it
+        assert_eq!(
+            mixins.ancestors("Order"),
+            vec![
+                "::Tracing",
+                "::Order",
+                "::Comparable",
+                "::Billing::Payable",
+                "::Auditable",
+                "::Loggable",
+            ]
+        );
+        assert_eq!(
+            mixins.extended_modules("Order"),
+            vec!["::Searchable", "::Loggable"]
+        );
+        assert_eq!(mixins.ancestors("::Loggable"), vec!["::Loggable"]);
+
+        // Comparable does not resolve, and is reopened in config/initializers
+        assert_eq!(
+            mixins.ancestors("Comparable"),
+            vec!["::Comparable", "::Loggable"]
+        );
+        assert_eq!(
+            mixins
+                .of("Comparable")
+                .iter()
+                .map(|mixin| mixin.module_name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["::Loggable"]
+        );
+        Ok(())
+    }
+}
@@ -2,29 +2,74 @@ pub(crate) mod cache;
 pub(crate) mod cached_file;
 pub mod configuration;
 pub(crate) mod constant_resolver;
+pub mod definition;
+pub mod dependency_graph;
+pub mod error;
+pub mod file_walker;
+pub mod inflections;
+pub mod inheritance;
+pub mod mixins;
+pub mod packs;
+pub(crate) mod packwerk_config;
 pub(crate) mod parser;
 pub mod reference;
+pub mod reference_index;
 pub(crate) mod zeitwerk;
+pub mod zeitwerk_check;
 
 pub(crate) mod common_test;
 
+pub use crate::references::parser::{
+    DiagnosticSeverity, MixinKind, ParseDiagnostic, SkipReason, SkippedConstruct,
+};
+
+use std::collections::HashSet;
+
 use crate::references::configuration::Configuration;
-use crate::references::parser::parse;
-use crate::references::reference::Reference;
-use crate::references::zeitwerk::get_zeitwerk_constant_resolver;
+use crate::references::definition::Definition;
+use crate::references::error::ReferencesError;
+use crate::references::parser::{parse, SourceLocation};
+use crate::references::reference::{Reference, ReferenceKind, SourceRange};
+use crate::references::zeitwerk::{autoloaded_constants, get_zeitwerk_constant_resolver};
 
-use anyhow::Context;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ReferencesReport {
+    pub references: Vec<Reference>,
+    // syntax errors and warnings of the included files, sorted by file and position
+    pub diagnostics: Vec<ParseDiagnostic>,
+    // metaprogramming the parser could not follow, sorted by file and position
+    pub skipped_constructs: Vec<SkippedConstruct>,
+}
+
+pub fn all_references(configuration: &Configuration) -> Result<Vec<Reference>, ReferencesError> {
+    Ok(all_references_with_diagnostics(configuration)?.references)
+}
 
-pub fn all_references(configuration: &Configuration) -> anyhow::Result<Vec<Reference>> {
-    let processed_files_to_check =
-        parse(configuration).context("failed to parse processed files")?;
-    let constant_resolver = get_zeitwerk_constant_resolver(configuration);
+/// Like `all_references`, also returning what the parser reported about files it could not
+/// fully parse. References found before a syntax error are kept whenever the parser recovers.
+pub fn all_references_with_diagnostics(
+    configuration: &Configuration,
+) -> Result<ReferencesReport, ReferencesError> {
+    let processed_files_to_check = parse(configuration)?;
+    let constant_resolver =
+        get_zeitwerk_constant_resolver(configuration, &processed_files_to_check)?;
 
-    let references: anyhow::Result<Vec<Reference>> = processed_files_to_check
+    let references: Result<Vec<Reference>, ReferencesError> = processed_files_to_check
         .par_iter()
         .try_fold(Vec::new, |mut acc, processed_file| {
             for unresolved_ref in processed_file.unresolved_references.iter() {
+                // Always collected, so that toggling these options does not need a fresh cache
+                let excluded = match unresolved_ref.kind {
+                    ReferenceKind::Dynamic => !configuration.string_constant_references,
+                    ReferenceKind::TypeOnly => configuration.exclude_type_only_references,
+                    _ => false,
+                };
+                if excluded {
+                    continue;
+                }
                 let new_references = Reference::from_unresolved_reference(
                     configuration,
                     constant_resolver.as_ref(),
@@ -39,17 +84,78 @@ pub fn all_references(configuration: &Configuration) -> anyhow::Result<Vec<Refer
             acc.append(&mut vec);
             Ok(acc)
         });
-    references
+
+    let mut diagnostics = vec![];
+    let mut skipped_constructs = vec![];
+    for processed_file in processed_files_to_check {
+        diagnostics.extend(processed_file.diagnostics);
+        skipped_constructs.extend(processed_file.skipped_constructs);
+    }
+    diagnostics.sort();
+    skipped_constructs.sort_by(|a, b| {
+        (&a.file, a.location.start_row, a.location.start_col).cmp(&(
+            &b.file,
+            b.location.start_row,
+            b.location.start_col,
+        ))
+    });
+
+    Ok(ReferencesReport {
+        references: references?,
+        diagnostics,
+        skipped_constructs,
+    })
+}
+
+/// Every class, module and constant assignment the parser finds in the included files,
+/// sorted by file and position.
+pub fn all_definitions(configuration: &Configuration) -> Result<Vec<Definition>, ReferencesError> {
+    let processed_files = parse(configuration)?;
+    let inferred = autoloaded_constants(configuration)?
+        .into_iter()
+        .map(|constant| {
+            (
+                constant.fully_qualified_name,
+                constant.absolute_path_of_definition,
+            )
+        })
+        .collect::<HashSet<_>>();
+
+    let mut definitions = vec![];
+    for processed_file in processed_files {
+        let relative_defining_file = configuration.relative_path(&processed_file.absolute_path)?;
+        for definition in processed_file.definitions {
+            let inferred_by_zeitwerk = inferred.contains(&(
+                definition.fully_qualified_name.to_owned(),
+                processed_file.absolute_path.to_owned(),
+            ));
+            definitions.push(Definition {
+                source_location: SourceLocation {
+                    line: definition.location.start_row,
+                    column: definition.location.start_col,
+                },
+                source_range: SourceRange::from_range(
+                    &definition.location,
+                    configuration.position_base,
+                ),
+                fully_qualified_name: definition.fully_qualified_name,
+                relative_defining_file: relative_defining_file.to_owned(),
+                inferred_by_zeitwerk,
+            });
+        }
+    }
+    definitions.sort_by(|a, b| {
+        (&a.relative_defining_file, &a.source_location)
+            .cmp(&(&b.relative_defining_file, &b.source_location))
+    });
+
+    Ok(definitions)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
-    use std::fs;
-
     use super::*;
     use crate::references::common_test::common_test::{configuration_for_fixture, SIMPLE_APP};
-    use parser::SourceLocation;
     use pretty_assertions::assert_eq;
 
     fn expected_from_references_json(
@@ -95,4 +201,321 @@ mod tests {
         )?;
         test_references("tests/fixtures/app_with_rails_relationships", expected)
     }
+
+    #[test]
+    fn parsed_definition_references() -> anyhow::Result<()> {
+        let expected = expected_from_references_json(
+            "tests/fixtures/app_with_parsed_definitions/references.json",
+        )?;
+        test_references("tests/fixtures/app_with_parsed_definitions", expected)
+    }
+
+    #[test]
+    fn unreadable_file_error() {
+        let mut configuration = configuration_for_fixture(SIMPLE_APP, false);
+        let missing = configuration.absolute_root.join("app/services/missing.rb");
+        configuration.included_files.insert(missing.clone());
+
+        match all_references(&configuration) {
+            Err(ReferencesError::UnreadableFile { path, .. }) => assert_eq!(path, missing),
+            result => panic!("expected an unreadable file error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn path_outside_root_error() {
+        let mut configuration = configuration_for_fixture(SIMPLE_APP, false);
+        let outside =
+            std::path::PathBuf::from("tests/fixtures/small-app/app/models/client_invitation.rb")
+                .canonicalize()
+                .unwrap();
+        configuration.included_files.insert(outside.clone());
+
+        match all_references(&configuration) {
+            Err(ReferencesError::PathOutsideRoot {
+                path,
+                absolute_root,
+            }) => {
+                assert_eq!(path, outside);
+                assert_eq!(absolute_root, configuration.absolute_root);
+            }
+            result => panic!("expected a path outside root error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn syntax_error_diagnostics() -> anyhow::Result<()> {
+        let configuration =
+            configuration_for_fixture("tests/fixtures/app_with_syntax_errors", false);
+        let report = all_references_with_diagnostics(&configuration)?;
+
+        let reference_names = report
+            .references
+            .iter()
+            .map(|reference| reference.constant_name.as_str())
+            .collect::<Vec<&str>>();
+        assert!(
+            reference_names.contains(&"::Customer"),
+            "{:?}",
+            reference_names
+        );
+        assert_eq!(
+            report.diagnostics,
+            vec![ParseDiagnostic {
+                file: configuration.absolute_root.join("app/models/order.rb"),
+                line: 4,
+                column: 2,
+                message: String::from("unexpected kEND"),
+                severity: DiagnosticSeverity::Error,
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn strict_parse() {
+        let mut configuration =
+            configuration_for_fixture("tests/fixtures/app_with_syntax_errors", false);
+        configuration.strict_parse = true;
+
+        match all_references(&configuration) {
+            Err(ReferencesError::ParseFailed { diagnostics }) => {
+                assert_eq!(diagnostics.len(), 1);
+                assert!(diagnostics[0].file.ends_with("app/models/order.rb"));
+            }
+            result => panic!("expected a parse failure, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn skipped_metaprogramming() -> anyhow::Result<()> {
+        let configuration =
+            configuration_for_fixture("tests/fixtures/app_with_metaprogramming", false);
+        let report = all_references_with_diagnostics(&configuration)?;
+
+        let invoice = report
+            .references
+            .iter()
+            .find(|reference| reference.constant_name == "::Legacy::Invoice")
+            .expect("Legacy::Invoice is resolved through a bracketed autoload path");
+        assert_eq!(
+            invoice.relative_defining_file.as_deref(),
+            Some("lib/[legacy]/invoice.rb")
+        );
+
+        let skipped = report
+            .skipped_constructs
+            .iter()
+            .map(|skipped| (skipped.location.start_row, skipped.reason))
+            .collect::<Vec<(usize, SkipReason)>>();
+        assert_eq!(
+            skipped,
+            vec![
+                (5, SkipReason::DynamicConstantScope),
+                (9, SkipReason::DynamicClassName),
+                (12, SkipReason::DynamicModuleName),
+                (15, SkipReason::DynamicConstantScope),
+            ]
+        );
+        assert!(report.skipped_constructs[0]
+            .file
+            .ends_with("app/models/report.rb"));
+        Ok(())
+    }
+
+    #[test]
+    fn string_constant_references() -> anyhow::Result<()> {
+        let mut configuration =
+            configuration_for_fixture("tests/fixtures/app_with_metaprogramming", false);
+        let dynamic_references = |references: Vec<Reference>| {
+            references
+                .into_iter()
+                .filter(|reference| reference.kind == ReferenceKind::Dynamic)
+                .map(|reference| {
+                    (
+                        reference.constant_name,
+                        reference.relative_defining_file,
+                        reference.source_location.line,
+                    )
+                })
+                .collect::<Vec<(String, Option<String>, usize)>>()
+        };
+
+        assert!(dynamic_references(all_references(&configuration)?).is_empty());
+
+        configuration.string_constant_references = true;
+        let mut references = dynamic_references(all_references(&configuration)?);
+        references.sort();
+        assert_eq!(
+            references,
+            vec![
+                (
+                    String::from("::Legacy::Invoice"),
+                    Some(String::from("lib/[legacy]/invoice.rb")),
+                    3
+                ),
+                (
+                    String::from("::Report"),
+                    Some(String::from("app/models/report.rb")),
+                    4
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn exclude_type_only_references() -> anyhow::Result<()> {
+        let mut configuration = configuration_for_fixture("tests/fixtures/app_with_sorbet", false);
+        assert!(configuration.exclude_type_only_references);
+        let constant_names = |references: Vec<Reference>| {
+            let mut names = references
+                .into_iter()
+                // leaves out definitions and the unresolved Sorbet runtime constants
+                .filter(|reference| {
+                    reference.kind != ReferenceKind::Definition
+                        && reference.relative_defining_file.is_some()
+                })
+                .map(|reference| (reference.constant_name, reference.kind))
+                .collect::<Vec<(String, ReferenceKind)>>();
+            names.sort();
+            names
+        };
+
+        assert_eq!(
+            constant_names(all_references(&configuration)?),
+            vec![(String::from("::Ledger"), ReferenceKind::Constant)]
+        );
+
+        configuration.exclude_type_only_references = false;
+        assert_eq!(
+            constant_names(all_references(&configuration)?),
+            vec![
+                (String::from("::Currency"), ReferenceKind::TypeOnly),
+                (String::from("::Ledger"), ReferenceKind::Constant),
+                (String::from("::Ledger"), ReferenceKind::TypeOnly),
+                (String::from("::Money"), ReferenceKind::TypeOnly),
+                (String::from("::Money"), ReferenceKind::TypeOnly),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn zero_based_source_range() -> anyhow::Result<()> {
+        let mut configuration = configuration_for_fixture(SIMPLE_APP, false);
+        configuration.position_base = reference::PositionBase::ZeroBased;
+        let references = all_references(&configuration)?;
+
+        // `module Bar` on the first line of bar.rb
+        let bar = references
+            .iter()
+            .find(|reference| {
+                reference.constant_name == "::Bar"
+                    && reference.relative_referencing_file == "packs/bar/app/services/bar.rb"
+            })
+            .unwrap();
+        assert_eq!(
+            bar.source_range,
+            reference::SourceRange {
+                start: reference::SourcePosition {
+                    line: 0,
+                    column: 7,
+                    utf16_column: 7,
+                    byte_offset: 7,
+                },
+                end: reference::SourcePosition {
+                    line: 0,
+                    column: 10,
+                    utf16_column: 10,
+                    byte_offset: 10,
+                },
+            }
+        );
+        assert_eq!(bar.source_location.line, 1);
+        assert_eq!(bar.source_location.column, 7);
+        Ok(())
+    }
+
+    #[test]
+    fn definitions() -> anyhow::Result<()> {
+        let configuration = configuration_for_fixture("tests/fixtures/app_with_inheritance", false);
+        let definitions = all_definitions(&configuration)?
+            .into_iter()
+            .map(|definition| {
+                (
+                    definition.relative_defining_file,
+                    definition.fully_qualified_name,
+                    definition.source_location.line,
+                    definition.inferred_by_zeitwerk,
+                )
+            })
+            .collect::<Vec<(String, String, usize, bool)>>();
+        let expected = [
+            ("app/models/admin.rb", "::Admin", 1, true),
+            (
+                "app/models/application_record.rb",
+                "::ApplicationRecord",
+                1,
+                true,
+            ),
+            ("app/models/billing/invoice.rb", "::Billing", 1, false),
+            (
+                "app/models/billing/invoice.rb",
+                "::Billing::Invoice",
+                2,
+                true,
+            ),
+            ("app/models/billing/refund.rb", "::Billing", 1, false),
+            ("app/models/billing/refund.rb", "::Billing::Refund", 2, true),
+            ("app/models/user.rb", "::User", 1, true),
+            ("app/models/user.rb", "::User::ROLES", 2, false),
+        ]
+        .into_iter()
+        .map(|(file, name, line, inferred)| (file.to_owned(), name.to_owned(), line, inferred))
+        .collect::<Vec<(String, String, usize, bool)>>();
+        assert_eq!(definitions, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn template_references() -> anyhow::Result<()> {
+        let configuration = Configuration::from_packwerk_yml(std::path::Path::new(
+            "tests/fixtures/app_with_templates",
+        ))?;
+        let references = all_references(&configuration)?
+            .into_iter()
+            .map(|reference| {
+                (
+                    reference.relative_referencing_file,
+                    reference.constant_name,
+                    reference.relative_defining_file,
+                    reference.kind,
+                )
+            })
+            .collect::<Vec<(String, String, Option<String>, ReferenceKind)>>();
+
+        let order = Some(String::from("app/models/order.rb"));
+        assert!(references.contains(&(
+            String::from("app/views/orders/show.html.haml"),
+            String::from("::Order"),
+            order.clone(),
+            ReferenceKind::Haml
+        )));
+        assert!(references.contains(&(
+            String::from("app/views/orders/index.html.slim"),
+            String::from("::Order"),
+            order,
+            ReferenceKind::Slim
+        )));
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_definition_references() -> anyhow::Result<()> {
+        let expected = expected_from_references_json(
+            "tests/fixtures/app_with_duplicate_definitions/references.json",
+        )?;
+        test_references("tests/fixtures/app_with_duplicate_definitions", expected)
+    }
 }
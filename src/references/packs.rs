@@ -0,0 +1,232 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use walkdir::WalkDir;
+use yaml_rust::{Yaml, YamlLoader};
+
+use crate::references::{
+    error::ReferencesError,
+    packwerk_config::{build_glob_set, string_map},
+};
+
+pub const PACKAGE_YML: &str = "package.yml";
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Pack {
+    // The pack directory relative to the application root, "." for the root pack
+    pub name: String,
+    pub absolute_path: PathBuf,
+    // absolute autoload root => default namespace, read from the `autoload_roots` key of package.yml.
+    // These take precedence over the default autoload roots.
+    pub autoload_roots: HashMap<PathBuf, String>,
+}
+
+impl Pack {
+    pub fn from_package_yml(
+        absolute_root: &Path,
+        package_yml: &Path,
+    ) -> Result<Pack, ReferencesError> {
+        let outside_root = || ReferencesError::PathOutsideRoot {
+            path: package_yml.to_path_buf(),
+            absolute_root: absolute_root.to_path_buf(),
+        };
+        let absolute_path = package_yml.parent().ok_or_else(outside_root)?.to_path_buf();
+        let name = match absolute_path.strip_prefix(absolute_root) {
+            Ok(relative) if relative.as_os_str().is_empty() => String::from("."),
+            Ok(relative) => relative.to_string_lossy().to_string(),
+            Err(_) => return Err(outside_root()),
+        };
+
+        let contents =
+            fs::read_to_string(package_yml).map_err(|source| ReferencesError::UnreadableFile {
+                path: package_yml.to_path_buf(),
+                source,
+            })?;
+        let yaml = YamlLoader::load_from_str(&contents)
+            .map_err(|e| ReferencesError::Config {
+                path: Some(package_yml.to_path_buf()),
+                key: None,
+                message: e.to_string(),
+            })?
+            .into_iter()
+            .next()
+            .unwrap_or(Yaml::Null);
+        let autoload_roots = string_map(&yaml, "autoload_roots", package_yml)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(path, namespace)| (absolute_path.join(path), namespace))
+            .collect();
+
+        Ok(Pack {
+            name,
+            absolute_path,
+            autoload_roots,
+        })
+    }
+
+    // Mirrors the Rails defaults: every directory in `app/` and every `app/*/concerns`
+    // is an autoload root for the top-level namespace.
+    pub fn default_autoload_roots(&self) -> Vec<PathBuf> {
+        let mut roots = vec![];
+        for app_dir in subdirectories(&self.absolute_path.join("app")) {
+            let concerns_dir = app_dir.join("concerns");
+            roots.push(app_dir);
+            if concerns_dir.is_dir() {
+                roots.push(concerns_dir);
+            }
+        }
+        roots
+    }
+
+    pub fn all_autoload_roots(&self) -> HashMap<PathBuf, String> {
+        let mut roots: HashMap<PathBuf, String> = self
+            .default_autoload_roots()
+            .into_iter()
+            .map(|root| (root, String::from("")))
+            .collect();
+        for (root, namespace) in &self.autoload_roots {
+            roots.insert(root.to_owned(), namespace.to_owned());
+        }
+        roots
+    }
+}
+
+/// Finds every package.yml under `absolute_root` whose directory matches one of the
+/// `package_paths` globs and which is not matched by an `exclude` glob.
+/// Packs are sorted by name.
+pub fn discover_packs(
+    absolute_root: &Path,
+    package_paths: &[String],
+    exclude: &[String],
+) -> Result<Vec<Pack>, ReferencesError> {
+    let package_yml_globs = package_paths
+        .iter()
+        .map(|package_path| {
+            let package_path = package_path.trim_end_matches('/');
+            if package_path.is_empty() || package_path == "." {
+                String::from(PACKAGE_YML)
+            } else {
+                format!("{}/{}", package_path, PACKAGE_YML)
+            }
+        })
+        .collect::<Vec<String>>();
+    let package_yml_set = build_glob_set(&package_yml_globs)
+        .map_err(|e| ReferencesError::invalid_key(None, "package_paths", format!("{:#}", e)))?;
+    let exclude_set = build_glob_set(exclude)
+        .map_err(|e| ReferencesError::invalid_key(None, "exclude", format!("{:#}", e)))?;
+
+    let mut packs = WalkDir::new(absolute_root)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != "node_modules")
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() && entry.file_name() == PACKAGE_YML)
+        .filter(|entry| {
+            let relative_path = entry
+                .path()
+                .strip_prefix(absolute_root)
+                .unwrap_or(entry.path());
+            package_yml_set.is_match(relative_path) && !exclude_set.is_match(relative_path)
+        })
+        .map(|entry| Pack::from_package_yml(absolute_root, entry.path()))
+        .collect::<Result<Vec<Pack>, ReferencesError>>()?;
+    packs.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(packs)
+}
+
+/// Combines the autoload roots of every pack into the shape expected by
+/// `Configuration::autoload_paths`.
+pub fn autoload_paths(packs: &[Pack]) -> HashMap<PathBuf, String> {
+    packs.iter().flat_map(Pack::all_autoload_roots).collect()
+}
+
+fn subdirectories(path: &Path) -> Vec<PathBuf> {
+    let mut directories = match fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect::<Vec<PathBuf>>(),
+        Err(_) => vec![],
+    };
+    directories.sort();
+    directories
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::references::common_test::common_test::{get_absolute_root, SIMPLE_APP};
+    use pretty_assertions::assert_eq;
+
+    fn default_package_paths() -> Vec<String> {
+        vec![String::from("**/")]
+    }
+
+    #[test]
+    fn discovers_packs() -> anyhow::Result<()> {
+        let absolute_root = get_absolute_root(SIMPLE_APP);
+        let packs = discover_packs(&absolute_root, &default_package_paths(), &[])?;
+        let names = packs.iter().map(|p| p.name.as_str()).collect::<Vec<&str>>();
+        assert_eq!(names, vec![".", "packs/bar", "packs/baz", "packs/foo"]);
+        Ok(())
+    }
+
+    #[test]
+    fn package_paths_and_exclude() -> anyhow::Result<()> {
+        let absolute_root = get_absolute_root(SIMPLE_APP);
+        let packs = discover_packs(
+            &absolute_root,
+            &[String::from("packs/*")],
+            &[String::from("packs/baz/**/*")],
+        )?;
+        let names = packs.iter().map(|p| p.name.as_str()).collect::<Vec<&str>>();
+        assert_eq!(names, vec!["packs/bar", "packs/foo"]);
+        Ok(())
+    }
+
+    #[test]
+    fn default_autoload_roots() -> anyhow::Result<()> {
+        let absolute_root = get_absolute_root(SIMPLE_APP);
+        let pack =
+            Pack::from_package_yml(&absolute_root, &absolute_root.join("packs/bar/package.yml"))?;
+        assert_eq!(
+            pack.default_autoload_roots(),
+            vec![
+                absolute_root.join("packs/bar/app/models"),
+                absolute_root.join("packs/bar/app/models/concerns"),
+                absolute_root.join("packs/bar/app/services"),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pack_autoload_roots_override_defaults() -> anyhow::Result<()> {
+        let absolute_root = get_absolute_root("tests/fixtures/app_with_pack_autoload_roots");
+        let packs = discover_packs(&absolute_root, &default_package_paths(), &[])?;
+
+        assert_eq!(
+            autoload_paths(&packs),
+            HashMap::from([
+                (absolute_root.join("app/services"), String::from("")),
+                (
+                    absolute_root.join("packs/payments/app/services"),
+                    String::from("")
+                ),
+                (
+                    absolute_root.join("packs/payments/app/gateways"),
+                    String::from("::Payments::Gateways")
+                ),
+                (
+                    absolute_root.join("packs/payments/lib/payments"),
+                    String::from("::Payments")
+                ),
+            ])
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use yaml_rust::{Yaml, YamlLoader};
+
+use crate::references::error::ReferencesError;
+
+pub(crate) const PACKWERK_YML: &str = "packwerk.yml";
+
+// Defaults match https://github.com/Shopify/packwerk/blob/main/lib/packwerk/configuration.rb
+const DEFAULT_INCLUDE_GLOBS: [&str; 1] = ["**/*.{rb,rake,erb}"];
+const DEFAULT_EXCLUDE_GLOBS: [&str; 1] = ["{bin,node_modules,script,tmp,vendor}/**/*"];
+const DEFAULT_PACKAGE_PATHS: [&str; 1] = ["**/"];
+const DEFAULT_CACHE_DIRECTORY: &str = "tmp/cache/packwerk";
+
+// The subset of packwerk.yml this crate understands. Unknown keys are ignored
+// so that a packwerk.yml shared with other tools can be loaded as-is.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct PackwerkConfig {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub package_paths: Vec<String>,
+    pub custom_associations: Vec<String>,
+    pub cache: bool,
+    pub cache_directory: String,
+    // relative autoload root => default namespace, e.g. "app/company_data" => "::Company"
+    pub autoload_roots: Vec<(String, String)>,
+    // globs passed to Rails.autoloaders.main.collapse and .ignore
+    pub zeitwerk_collapse: Vec<String>,
+    pub zeitwerk_ignore: Vec<String>,
+    // relative paths of files that reopen a constant defined elsewhere
+    pub ignored_monkeypatches: Vec<String>,
+    pub index_parsed_definitions: bool,
+    pub strict_parse: bool,
+    pub string_constant_references: bool,
+    pub exclude_type_only_references: bool,
+}
+
+impl PackwerkConfig {
+    pub(crate) fn from_path(path: &Path) -> anyhow::Result<PackwerkConfig> {
+        let contents =
+            fs::read_to_string(path).map_err(|source| ReferencesError::UnreadableFile {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        let documents =
+            YamlLoader::load_from_str(&contents).map_err(|e| ReferencesError::Config {
+                path: Some(path.to_path_buf()),
+                key: None,
+                message: e.to_string(),
+            })?;
+        let yaml = documents.into_iter().next().unwrap_or(Yaml::Null);
+
+        match yaml {
+            Yaml::Hash(_) | Yaml::Null => {}
+            _ => {
+                return Err(ReferencesError::Config {
+                    path: Some(path.to_path_buf()),
+                    key: None,
+                    message: String::from("expected a mapping of configuration keys"),
+                }
+                .into())
+            }
+        }
+
+        Ok(PackwerkConfig {
+            include: glob_list(&yaml, "include", path)?
+                .unwrap_or_else(|| to_strings(&DEFAULT_INCLUDE_GLOBS)),
+            exclude: glob_list(&yaml, "exclude", path)?
+                .unwrap_or_else(|| to_strings(&DEFAULT_EXCLUDE_GLOBS)),
+            package_paths: glob_list(&yaml, "package_paths", path)?
+                .unwrap_or_else(|| to_strings(&DEFAULT_PACKAGE_PATHS)),
+            custom_associations: string_list(&yaml, "custom_associations", path)?
+                .unwrap_or_default(),
+            cache: boolean(&yaml, "cache", path)?.unwrap_or(false),
+            cache_directory: string(&yaml, "cache_directory", path)?
+                .unwrap_or_else(|| DEFAULT_CACHE_DIRECTORY.to_owned()),
+            autoload_roots: string_map(&yaml, "autoload_roots", path)?.unwrap_or_default(),
+            zeitwerk_collapse: glob_list(&yaml, "zeitwerk_collapse", path)?.unwrap_or_default(),
+            zeitwerk_ignore: glob_list(&yaml, "zeitwerk_ignore", path)?.unwrap_or_default(),
+            ignored_monkeypatches: string_list(&yaml, "ignored_monkeypatches", path)?
+                .unwrap_or_default(),
+            index_parsed_definitions: boolean(&yaml, "index_parsed_definitions", path)?
+                .unwrap_or(false),
+            strict_parse: boolean(&yaml, "strict_parse", path)?.unwrap_or(false),
+            string_constant_references: boolean(&yaml, "string_constant_references", path)?
+                .unwrap_or(false),
+            exclude_type_only_references: boolean(&yaml, "exclude_type_only_references", path)?
+                .unwrap_or(false),
+        })
+    }
+}
+
+pub(crate) fn build_glob_set(patterns: &[String]) -> anyhow::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        // Like Ruby's File.fnmatch with FNM_PATHNAME, `*` does not cross directory boundaries
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .context(format!("invalid glob pattern {:?}", pattern))?;
+        builder.add(glob);
+    }
+    Ok(builder.build()?)
+}
+
+fn to_strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+fn invalid_key(key: &str, path: &Path, expected: &str) -> anyhow::Error {
+    ReferencesError::invalid_key(Some(path), key, format!("expected {}", expected)).into()
+}
+
+// A missing key and a key without a value (`include:`) both fall back to the default
+fn value<'a>(yaml: &'a Yaml, key: &str) -> Option<&'a Yaml> {
+    match &yaml[key] {
+        Yaml::BadValue | Yaml::Null => None,
+        value => Some(value),
+    }
+}
+
+fn string(yaml: &Yaml, key: &str, path: &Path) -> anyhow::Result<Option<String>> {
+    match value(yaml, key) {
+        None => Ok(None),
+        Some(Yaml::String(s)) => Ok(Some(s.to_owned())),
+        Some(_) => Err(invalid_key(key, path, "a string")),
+    }
+}
+
+fn boolean(yaml: &Yaml, key: &str, path: &Path) -> anyhow::Result<Option<bool>> {
+    match value(yaml, key) {
+        None => Ok(None),
+        Some(Yaml::Boolean(b)) => Ok(Some(*b)),
+        Some(_) => Err(invalid_key(key, path, "true or false")),
+    }
+}
+
+// packwerk accepts either a single string or a list of strings for list-valued keys
+fn string_list(yaml: &Yaml, key: &str, path: &Path) -> anyhow::Result<Option<Vec<String>>> {
+    match value(yaml, key) {
+        None => Ok(None),
+        Some(Yaml::String(s)) => Ok(Some(vec![s.to_owned()])),
+        Some(Yaml::Array(items)) => items
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .map(String::from)
+                    .ok_or_else(|| invalid_key(key, path, "a string or a list of strings"))
+            })
+            .collect::<anyhow::Result<Vec<String>>>()
+            .map(Some),
+        Some(_) => Err(invalid_key(key, path, "a string or a list of strings")),
+    }
+}
+
+fn glob_list(yaml: &Yaml, key: &str, path: &Path) -> anyhow::Result<Option<Vec<String>>> {
+    let globs = string_list(yaml, key, path)?;
+    if let Some(globs) = &globs {
+        build_glob_set(globs)
+            .map_err(|e| ReferencesError::invalid_key(Some(path), key, format!("{:#}", e)))?;
+    }
+    Ok(globs)
+}
+
+pub(crate) fn string_map(
+    yaml: &Yaml,
+    key: &str,
+    path: &Path,
+) -> anyhow::Result<Option<Vec<(String, String)>>> {
+    match value(yaml, key) {
+        None => Ok(None),
+        Some(Yaml::Hash(entries)) => entries
+            .iter()
+            .map(|(k, v)| match (k.as_str(), v.as_str()) {
+                (Some(k), Some(v)) => Ok((k.to_owned(), v.to_owned())),
+                _ => Err(invalid_key(key, path, "a mapping of strings to strings")),
+            })
+            .collect::<anyhow::Result<Vec<(String, String)>>>()
+            .map(Some),
+        Some(_) => Err(invalid_key(key, path, "a mapping of strings to strings")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn defaults_for_commented_out_keys() -> anyhow::Result<()> {
+        let config = PackwerkConfig::from_path(&PathBuf::from(
+            "tests/fixtures/app_with_inflections/packwerk.yml",
+        ))?;
+        assert_eq!(
+            config,
+            PackwerkConfig {
+                include: vec!["**/*.{rb,rake,erb}".to_owned()],
+                exclude: vec!["{bin,node_modules,script,tmp,vendor}/**/*".to_owned()],
+                package_paths: vec!["**/".to_owned()],
+                custom_associations: vec![],
+                cache: false,
+                cache_directory: "tmp/cache/packwerk".to_owned(),
+                autoload_roots: vec![],
+                zeitwerk_collapse: vec![],
+                zeitwerk_ignore: vec![],
+                ignored_monkeypatches: vec![],
+                index_parsed_definitions: false,
+                strict_parse: false,
+                string_constant_references: false,
+                exclude_type_only_references: false,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_key_names_key_and_file() {
+        let path = PathBuf::from("tests/fixtures/invalid_packwerk_config/packwerk.yml");
+        let error = PackwerkConfig::from_path(&path).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "invalid value for `custom_associations` in tests/fixtures/invalid_packwerk_config/packwerk.yml: expected a string or a list of strings"
+        );
+    }
+
+    #[test]
+    fn missing_file_names_file() {
+        let path = PathBuf::from("tests/fixtures/does_not_exist/packwerk.yml");
+        let error = PackwerkConfig::from_path(&path).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ReferencesError>(),
+            Some(ReferencesError::UnreadableFile { path: error_path, .. }) if error_path == &path
+        ));
+    }
+}
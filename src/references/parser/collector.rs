@@ -1,8 +1,15 @@
+use std::path::Path;
+
 use lib_ruby_parser::{nodes, traverse::visitor::Visitor, Loc, Node};
-use line_col::LineColLookup;
-use std::collections::HashSet;
 
-use super::{inflector_shim::to_class_case, ParsedDefinition, Range, UnresolvedReference};
+use crate::references::{
+    constant_resolver::root_qualified, inflections::Inflections, reference::ReferenceKind,
+};
+
+use super::{
+    inflector_shim::to_class_case, line_index::LineIndex, MixinKind, ParsedDefinition, ParsedMixin,
+    ParsedSuperclass, Range, SkipReason, SkippedConstruct, UnresolvedReference,
+};
 
 #[derive(Debug)]
 pub enum ParseError {
@@ -16,29 +23,70 @@ pub struct SuperclassReference {
 }
 
 pub struct ReferenceCollector<'a> {
+    pub path: &'a Path,
     pub references: Vec<UnresolvedReference>,
     pub definitions: Vec<ParsedDefinition>,
+    pub skipped_constructs: Vec<SkippedConstruct>,
+    pub mixins: Vec<ParsedMixin>,
+    pub parsed_superclasses: Vec<ParsedSuperclass>,
     pub current_namespaces: Vec<String>,
-    pub line_col_lookup: LineColLookup<'a>,
+    pub line_index: LineIndex<'a>,
     pub in_superclass: bool,
+    pub in_mixin: bool,
+    // Within a Sorbet signature or type argument, where constants are only used as types
+    pub in_type: bool,
     pub superclasses: Vec<SuperclassReference>,
     pub custom_associations: Vec<String>,
+    pub inflections: &'a Inflections,
 }
 
 impl<'a> ReferenceCollector<'a> {
-    pub fn new(line_col_lookup: LineColLookup<'a>, custom_associations: Vec<String>) -> Self {
+    pub fn new(
+        path: &'a Path,
+        line_index: LineIndex<'a>,
+        custom_associations: Vec<String>,
+        inflections: &'a Inflections,
+    ) -> Self {
         ReferenceCollector {
+            path,
             references: vec![],
             definitions: vec![],
+            skipped_constructs: vec![],
+            mixins: vec![],
+            parsed_superclasses: vec![],
             current_namespaces: vec![],
-            line_col_lookup,
+            line_index,
             in_superclass: false,
+            in_mixin: false,
+            in_type: false,
             superclasses: vec![],
             custom_associations,
+            inflections,
         }
     }
+
+    fn visit_as_type(&mut self, node: &Node) {
+        let in_type = self.in_type;
+        self.in_type = true;
+        self.visit(node);
+        self.in_type = in_type;
+    }
+
+    fn skip(&mut self, loc: &Loc, reason: SkipReason) {
+        self.skipped_constructs.push(SkippedConstruct {
+            file: self.path.to_path_buf(),
+            location: loc_to_range(loc, &self.line_index),
+            reason,
+        });
+    }
 }
 
+// `T.let(value, Type)` and friends, whose second argument is a type
+const SORBET_TYPE_ASSERTION_METHOD_NAMES: [&str; 4] = ["let", "cast", "bind", "assert_type!"];
+
+// `prop :name, Type` and `const :name, Type` of T::Struct and T::Props
+const SORBET_PROP_METHOD_NAMES: [&str; 2] = ["prop", "const"];
+
 const ASSOCIATION_METHOD_NAMES: [&str; 4] = [
     "has_one",
     "has_many",
@@ -46,30 +94,37 @@ const ASSOCIATION_METHOD_NAMES: [&str; 4] = [
     "has_and_belongs_to_many",
 ];
 
+// Associations to a single record, whose names Rails does not singularize
+const SINGULAR_ASSOCIATION_METHOD_NAMES: [&str; 2] = ["has_one", "belongs_to"];
+
 impl<'a> Visitor for ReferenceCollector<'a> {
     fn on_class(&mut self, node: &nodes::Class) {
-        // We're not collecting definitions, so no need to visit the class definitioname);
-        let namespace_result = fetch_const_name(&node.name);
         // For now, we simply exit and stop traversing if we encounter an error when fetching the constant name of a class
         // We can iterate on this if this is different than the packwerk implementation
-        if namespace_result.is_err() {
+        let Ok(namespace) = fetch_const_name(&node.name) else {
+            self.skip(&node.expression_l, SkipReason::DynamicClassName);
             return;
-        }
-
-        let namespace = namespace_result.unwrap();
+        };
 
         if let Some(inner) = node.superclass.as_ref() {
             self.in_superclass = true;
             self.visit(inner);
             self.in_superclass = false;
         }
-        let definition_loc = fetch_node_location(&node.name).unwrap();
-        let location = loc_to_range(definition_loc, &self.line_col_lookup);
+        let location = loc_to_range(node.name.expression(), &self.line_index);
 
         let definition = get_definition_from(&namespace, &self.current_namespaces, &location);
 
         let name = definition.fully_qualified_name.to_owned();
         let namespace_path = self.current_namespaces.to_owned();
+        if let Some(Ok(superclass_name)) = node.superclass.as_deref().map(fetch_const_name) {
+            self.parsed_superclasses.push(ParsedSuperclass {
+                class_name: name.to_owned(),
+                superclass_name,
+                namespace_path: namespace_path.to_owned(),
+                location: location.clone(),
+            });
+        }
         self.definitions.push(definition);
 
         // Packwerk also considers a definition to be a "reference"
@@ -77,6 +132,7 @@ impl<'a> Visitor for ReferenceCollector<'a> {
             name,
             namespace_path,
             location,
+            kind: ReferenceKind::Definition,
         });
 
         // Note – is there a way to use lifetime specifiers to get rid of this and
@@ -96,26 +152,80 @@ impl<'a> Visitor for ReferenceCollector<'a> {
         let association_reference = get_reference_from_active_record_association(
             node,
             &self.current_namespaces,
-            &self.line_col_lookup,
+            &self.line_index,
             &self.custom_associations,
+            self.inflections,
         );
 
         if let Some(association_reference) = association_reference {
             self.references.push(association_reference);
         }
 
+        if let Some(string_constant_reference) =
+            get_reference_from_string_constant(node, &self.current_namespaces, &self.line_index)
+        {
+            self.references.push(string_constant_reference);
+        }
+
+        if let Some(kind) = mixin_kind(node) {
+            self.in_mixin = true;
+            for arg in &node.args {
+                if let Node::Const(module) = arg {
+                    if let Ok(module_name) = fetch_const_const_name(module) {
+                        self.mixins.push(ParsedMixin {
+                            class_name: current_class_name(&self.current_namespaces),
+                            module_name,
+                            namespace_path: self.current_namespaces.to_owned(),
+                            kind,
+                            location: loc_to_range(&module.expression_l, &self.line_index),
+                        });
+                    }
+                }
+                self.visit(arg);
+            }
+            self.in_mixin = false;
+            return;
+        }
+
+        if let Some(type_position) = sorbet_type_argument_position(node) {
+            if let Some(recv) = &node.recv {
+                self.visit(recv);
+            }
+            for (position, arg) in node.args.iter().enumerate() {
+                if position == type_position {
+                    self.visit_as_type(arg);
+                } else {
+                    self.visit(arg);
+                }
+            }
+            return;
+        }
+
         lib_ruby_parser::traverse::visitor::visit_send(self, node);
     }
 
+    fn on_block(&mut self, node: &nodes::Block) {
+        if !is_sorbet_type_block(&node.call) {
+            lib_ruby_parser::traverse::visitor::visit_block(self, node);
+            return;
+        }
+
+        self.visit(&node.call);
+        if let Some(body) = &node.body {
+            self.visit_as_type(body);
+        }
+    }
+
     fn on_casgn(&mut self, node: &nodes::Casgn) {
-        let definition = get_constant_assignment_definition(
+        match get_constant_assignment_definition(
             node,
             self.current_namespaces.to_owned(),
-            &self.line_col_lookup,
-        );
-
-        if let Some(definition) = definition {
-            self.definitions.push(definition);
+            &self.line_index,
+        ) {
+            Ok(definition) => self.definitions.push(definition),
+            Err(ParseError::Metaprogramming) => {
+                self.skip(&node.expression_l, SkipReason::DynamicConstantScope)
+            }
         }
 
         if let Some(v) = node.value.to_owned() {
@@ -128,10 +238,11 @@ impl<'a> Visitor for ReferenceCollector<'a> {
     }
 
     fn on_module(&mut self, node: &nodes::Module) {
-        let namespace = fetch_const_name(&node.name)
-            .expect("We expect no parse errors in class/module definitions");
-        let definition_loc = fetch_node_location(&node.name).unwrap();
-        let location = loc_to_range(definition_loc, &self.line_col_lookup);
+        let Ok(namespace) = fetch_const_name(&node.name) else {
+            self.skip(&node.expression_l, SkipReason::DynamicModuleName);
+            return;
+        };
+        let location = loc_to_range(node.name.expression(), &self.line_index);
 
         let definition = get_definition_from(&namespace, &self.current_namespaces, &location);
 
@@ -144,6 +255,7 @@ impl<'a> Visitor for ReferenceCollector<'a> {
             name,
             namespace_path,
             location,
+            kind: ReferenceKind::Definition,
         });
 
         // Note – is there a way to use lifetime specifiers to get rid of this and
@@ -160,6 +272,7 @@ impl<'a> Visitor for ReferenceCollector<'a> {
 
     fn on_const(&mut self, node: &nodes::Const) {
         let Ok(name) = fetch_const_const_name(node) else {
+            self.skip(&node.expression_l, SkipReason::DynamicConstantScope);
             return;
         };
 
@@ -192,14 +305,45 @@ impl<'a> Visitor for ReferenceCollector<'a> {
                 .collect::<Vec<String>>()
         };
 
+        let kind = if self.in_superclass {
+            ReferenceKind::Superclass
+        } else if self.in_mixin {
+            ReferenceKind::Mixin
+        } else if self.in_type {
+            ReferenceKind::TypeOnly
+        } else {
+            ReferenceKind::Constant
+        };
+
         self.references.push(UnresolvedReference {
             name,
             namespace_path,
-            location: loc_to_range(&node.expression_l, &self.line_col_lookup),
+            location: loc_to_range(&node.expression_l, &self.line_index),
+            kind,
         })
     }
 }
 
+fn mixin_kind(node: &nodes::Send) -> Option<MixinKind> {
+    if node.recv.is_some() {
+        return None;
+    }
+    match node.method_name.as_str() {
+        "include" => Some(MixinKind::Include),
+        "extend" => Some(MixinKind::Extend),
+        "prepend" => Some(MixinKind::Prepend),
+        _ => None,
+    }
+}
+
+fn current_class_name(current_namespaces: &[String]) -> String {
+    if current_namespaces.is_empty() {
+        String::from("::Object")
+    } else {
+        format!("::{}", current_namespaces.join("::"))
+    }
+}
+
 fn fetch_const_name(node: &nodes::Node) -> Result<String, ParseError> {
     match node {
         Node::Const(const_node) => Ok(fetch_const_const_name(const_node)?),
@@ -222,18 +366,6 @@ fn fetch_const_const_name(node: &nodes::Const) -> Result<String, ParseError> {
     }
 }
 
-fn fetch_node_location(node: &nodes::Node) -> Result<&Loc, ParseError> {
-    match node {
-        Node::Const(const_node) => Ok(&const_node.expression_l),
-        node => {
-            panic!(
-                "Cannot handle other node in get_constant_node_name: {:?}",
-                node
-            )
-        }
-    }
-}
-
 fn get_definition_from(
     current_nesting: &String,
     parent_nesting: &[String],
@@ -257,7 +389,7 @@ fn get_definition_from(
     }
 }
 
-fn loc_to_range(loc: &Loc, lookup: &LineColLookup) -> Range {
+fn loc_to_range(loc: &Loc, lookup: &LineIndex) -> Range {
     let (start_row, start_col) = lookup.get(loc.begin); // There's an off-by-one difference here with packwerk
     let (end_row, end_col) = lookup.get(loc.end);
 
@@ -266,96 +398,214 @@ fn loc_to_range(loc: &Loc, lookup: &LineColLookup) -> Range {
         start_col: start_col - 1,
         end_row,
         end_col,
+        start_byte: lookup.source_offset(loc.begin),
+        end_byte: lookup.source_offset(loc.end),
+        start_utf16_col: lookup.utf16_column(loc.begin),
+        end_utf16_col: lookup.utf16_column(loc.end),
     }
 }
 
 fn get_reference_from_active_record_association(
     node: &nodes::Send,
     current_namespaces: &[String],
-    line_col_lookup: &LineColLookup,
+    line_index: &LineIndex,
     custom_associations: &[String],
+    inflections: &Inflections,
 ) -> Option<UnresolvedReference> {
-    // TODO: Read in args, process associations as a separate class
-    // These can get complicated! e.g. we can specify a class name
-    let combined_associations: Vec<String> = custom_associations
-        .iter()
-        .map(|s| s.to_owned())
-        .chain(ASSOCIATION_METHOD_NAMES.iter().copied().map(String::from))
-        .collect();
-
-    let is_association = combined_associations
-        .iter()
-        .any(|association_method| node.method_name == *association_method);
-
-    if is_association {
-        let first_arg: Option<&Node> = node.args.first();
-
-        let mut name: Option<String> = None;
-        for node in node.args.iter() {
-            if let Node::Kwargs(kwargs) = node {
-                if let Some(found) = extract_class_name_from_kwargs(kwargs) {
-                    name = Some(found);
+    let method_name = node.method_name.as_str();
+    let is_association = ASSOCIATION_METHOD_NAMES.contains(&method_name)
+        || custom_associations.iter().any(|name| name == method_name);
+    if !is_association {
+        return None;
+    }
+
+    let options = AssociationOptions::from_args(&node.args);
+    // A polymorphic `belongs_to` may point at any class
+    if options.polymorphic {
+        return None;
+    }
+
+    // Mirrors how ActiveRecord picks the class of an association: an explicit `class_name:`
+    // wins, then the `source_type:` of a polymorphic source, then the `source:` association
+    // of a `through:` association, and finally the association name itself.
+    let name = if let Some(class_name) = options.class_name.or(options.source_type) {
+        // e.g. an interpolated string, which names a class we cannot know statically
+        class_name_from(class_name)?
+    } else if let Some(source) = options.source.and_then(symbol_or_string) {
+        to_class_case(&source, true, inflections)
+    } else if let Some(Node::Sym(sym)) = node.args.first() {
+        // By convention Rails singularizes the names of collection associations,
+        // e.g. `has_many :companies` will look for a class named `Company`, not `Companies`
+        let should_singularize = !SINGULAR_ASSOCIATION_METHOD_NAMES.contains(&method_name);
+        to_class_case(&sym.name.to_string_lossy(), should_singularize, inflections)
+    } else {
+        return None;
+    };
+
+    Some(UnresolvedReference {
+        name,
+        namespace_path: current_namespaces.to_owned(),
+        location: loc_to_range(&node.expression_l, line_index),
+        kind: ReferenceKind::Association,
+    })
+}
+
+// The options of an association declaration that determine its class
+#[derive(Default)]
+struct AssociationOptions<'a> {
+    class_name: Option<&'a Node>,
+    source: Option<&'a Node>,
+    source_type: Option<&'a Node>,
+    polymorphic: bool,
+}
+
+impl<'a> AssociationOptions<'a> {
+    fn from_args(args: &'a [Node]) -> Self {
+        let mut options = AssociationOptions::default();
+        for arg in args {
+            let Node::Kwargs(kwargs) = arg else {
+                continue;
+            };
+            for pair_node in &kwargs.pairs {
+                let Node::Pair(pair) = pair_node else {
+                    continue;
+                };
+                let Node::Sym(key) = &*pair.key else {
+                    continue;
+                };
+                match key.name.to_string_lossy().as_str() {
+                    "class_name" => options.class_name = Some(&pair.value),
+                    "source" => options.source = Some(&pair.value),
+                    "source_type" => options.source_type = Some(&pair.value),
+                    "polymorphic" => options.polymorphic = matches!(*pair.value, Node::True(_)),
+                    _ => {}
                 }
             }
         }
+        options
+    }
+}
 
-        if let Some(Node::Sym(d)) = first_arg {
-            if name.is_none() {
-                // We singularize here because by convention Rails will singularize the class name as declared via a symbol,
-                // e.g. `has_many :companies` will look for a class named `Company`, not `Companies`
-                name = Some(to_class_case(
-                    &d.name.to_string_lossy(),
-                    true,
-                    &HashSet::new(), // todo: pass in acronyms here
-                ));
+// `class_name: "Foo"`, `class_name: :Foo`, `class_name: "::Foo"` or `class_name: Foo.name`.
+// A leading `::` is kept so that the name resolves from the root namespace.
+fn class_name_from(node: &Node) -> Option<String> {
+    match node {
+        Node::Send(send)
+            if send.args.is_empty() && ["name", "to_s"].contains(&send.method_name.as_str()) =>
+        {
+            match send.recv.as_deref() {
+                Some(Node::Const(constant)) => fetch_const_const_name(constant).ok(),
+                _ => None,
             }
         }
+        _ => symbol_or_string(node),
+    }
+}
 
-        if name.is_some() {
-            let unwrapped_name = name.unwrap_or_else(|| {
-                panic!("Could not find class name for association {:?}", &node,)
-            });
-
-            Some(UnresolvedReference {
-                name: unwrapped_name,
-                namespace_path: current_namespaces.to_owned(),
-                location: loc_to_range(&node.expression_l, line_col_lookup),
-            })
-        } else {
-            None
-        }
-    } else {
-        None
+fn symbol_or_string(node: &Node) -> Option<String> {
+    match node {
+        Node::Str(string) => Some(string.value.to_string_lossy()),
+        Node::Sym(sym) => Some(sym.name.to_string_lossy()),
+        _ => None,
     }
 }
 
-fn extract_class_name_from_kwargs(kwargs: &nodes::Kwargs) -> Option<String> {
-    for pair_node in kwargs.pairs.iter() {
-        if let Node::Pair(pair) = pair_node {
-            if let Node::Sym(k) = *pair.key.to_owned() {
-                if k.name.to_string_lossy() == *"class_name" {
-                    if let Node::Str(v) = *pair.value.to_owned() {
-                        return Some(v.value.to_string_lossy());
+// `"Foo::Bar".constantize`, `"Foo".safe_constantize`, `Object.const_get("Foo")`,
+// `Foo.const_get(:Bar)` or `const_get(:Bar)`. Only literal names are followed.
+fn get_reference_from_string_constant(
+    node: &nodes::Send,
+    current_namespaces: &[String],
+    line_index: &LineIndex,
+) -> Option<UnresolvedReference> {
+    let (name, namespace_path, literal) = match node.method_name.as_str() {
+        "constantize" | "safe_constantize" if node.args.is_empty() => {
+            let literal = node.recv.as_deref()?;
+            let name = symbol_or_string(literal).filter(|name| is_constant_path(name))?;
+            // Like `Object.const_get`, `constantize` starts from the root namespace
+            (root_qualified(&name), vec![], literal)
+        }
+        "const_get" => {
+            let literal = node.args.first()?;
+            let name = symbol_or_string(literal).filter(|name| is_constant_path(name))?;
+            match node.recv.as_deref() {
+                None | Some(Node::Self_(_)) => (name, current_namespaces.to_owned(), literal),
+                Some(Node::Const(receiver)) => {
+                    let receiver = fetch_const_const_name(receiver).ok()?;
+                    if receiver == "Object" || receiver == "::Object" || name.starts_with("::") {
+                        (root_qualified(&name), vec![], literal)
+                    } else {
+                        (
+                            format!("{}::{}", receiver, name),
+                            current_namespaces.to_owned(),
+                            literal,
+                        )
                     }
                 }
+                _ => return None,
             }
         }
-    }
+        _ => return None,
+    };
 
+    Some(UnresolvedReference {
+        name,
+        namespace_path,
+        location: loc_to_range(literal.expression(), line_index),
+        kind: ReferenceKind::Dynamic,
+    })
+}
+
+// e.g. `Foo`, `::Foo` or `Foo::Bar`, but not `foo` or `Foo#{bar}`
+fn is_constant_path(name: &str) -> bool {
+    let name = name.strip_prefix("::").unwrap_or(name);
+    name.split("::").all(|segment| {
+        segment.starts_with(|c: char| c.is_ascii_uppercase())
+            && segment.chars().all(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
+// The position of the argument of `T.let(value, Type)` or `prop :name, Type` that is a type
+fn sorbet_type_argument_position(node: &nodes::Send) -> Option<usize> {
+    let method_name = node.method_name.as_str();
+    if SORBET_TYPE_ASSERTION_METHOD_NAMES.contains(&method_name) && is_sorbet_t(&node.recv) {
+        return Some(1);
+    }
+    if SORBET_PROP_METHOD_NAMES.contains(&method_name)
+        && node.recv.is_none()
+        && matches!(node.args.first(), Some(Node::Sym(_)))
+    {
+        return Some(1);
+    }
     None
 }
 
+// `sig { ... }`, `T::Sig::WithoutRuntime.sig { ... }` or `T.type_alias { ... }`
+fn is_sorbet_type_block(call: &Node) -> bool {
+    match call {
+        Node::Send(send) => match send.method_name.as_str() {
+            "sig" => matches!(send.recv.as_deref(), None | Some(Node::Const(_))),
+            "type_alias" => is_sorbet_t(&send.recv),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn is_sorbet_t(recv: &Option<Box<Node>>) -> bool {
+    match recv.as_deref() {
+        Some(Node::Const(constant)) => {
+            constant.name == "T" && matches!(constant.scope.as_deref(), None | Some(Node::Cbase(_)))
+        }
+        _ => false,
+    }
+}
+
 fn get_constant_assignment_definition(
     node: &nodes::Casgn,
     current_namespaces: Vec<String>,
-    line_col_lookup: &LineColLookup,
-) -> Option<ParsedDefinition> {
-    let name_result = fetch_casgn_name(node);
-    if name_result.is_err() {
-        return None;
-    }
-
-    let name = name_result.unwrap();
+    line_index: &LineIndex,
+) -> Result<ParsedDefinition, ParseError> {
+    let name = fetch_casgn_name(node)?;
     let fully_qualified_name = if !current_namespaces.is_empty() {
         let mut name_components = current_namespaces;
         name_components.push(name);
@@ -364,9 +614,9 @@ fn get_constant_assignment_definition(
         format!("::{}", name)
     };
 
-    Some(ParsedDefinition {
+    Ok(ParsedDefinition {
         fully_qualified_name,
-        location: loc_to_range(&node.expression_l, line_col_lookup),
+        location: loc_to_range(&node.expression_l, line_index),
     })
 }
 
@@ -0,0 +1,70 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use super::source_map::ExtractedRuby;
+
+// Like Erubi: `<%=` and `<%==` output, `<%-` and `-%>` trim whitespace, `<%#` is a comment
+// and `<%%` is an escaped, literal `<%`
+static ERB_TAG_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<%(==?|-|#|%)?(.*?)-?%>").unwrap());
+
+// The Ruby code of an ERB template, one tag per line
+pub(crate) fn extract_ruby(template: &str) -> ExtractedRuby {
+    let mut extracted = ExtractedRuby::default();
+    for capture in ERB_TAG_REGEX.captures_iter(template) {
+        if let Some("#" | "%") = capture.get(1).map(|indicator| indicator.as_str()) {
+            continue;
+        }
+        if let Some(code) = capture.get(2) {
+            extracted.push_snippet(code.start(), code.as_str());
+        }
+    }
+    extracted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippets(template: &str) -> Vec<String> {
+        extract_ruby(template)
+            .ruby
+            .lines()
+            .map(|line| line.trim().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(
+            snippets("<p><%= user.name %></p>\n<%== raw_html %>\n<% if admin? -%>\n  <%- Audit.log -%>\n<% end %>"),
+            vec!["user.name", "raw_html", "if admin?", "Audit.log", "end"]
+        );
+    }
+
+    #[test]
+    fn comments_and_escapes() {
+        assert_eq!(
+            snippets("<%# Legacy::Widget.render %>\n<%% Literal::Text %>\n<%= Current::User %>"),
+            vec!["Current::User"]
+        );
+    }
+
+    #[test]
+    fn trim_marker_is_not_code() {
+        let extracted = extract_ruby("<% items.each do |item| -%>");
+        assert_eq!(extracted.ruby, " items.each do |item| ");
+    }
+
+    #[test]
+    fn maps_back_to_the_template() {
+        let template = "<h1>Title</h1>\n<p>\n  <%= Admin::User.count %>\n</p>";
+        let extracted = extract_ruby(template);
+        let ruby_offset = extracted.ruby.find("Admin").unwrap();
+        assert_eq!(
+            extracted.source_map.template_offset(ruby_offset),
+            template.find("Admin").unwrap()
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use super::{
+    indented_template::{
+        closing_bracket, code_end, line_end, lines, push_interpolations, Blocks, Line,
+    },
+    source_map::ExtractedRuby,
+};
+
+// The Ruby code of a HAML template: `-` and `=` lines, the code after `%tag=`, attribute
+// hashes and object references, and `#{}` interpolation in plain text. Comments (`-#`, `/`)
+// and filters (`:javascript`) are skipped along with the lines nested in them.
+pub(crate) fn extract_ruby(template: &str) -> ExtractedRuby {
+    let mut extracted = ExtractedRuby::default();
+    let mut blocks = Blocks::default();
+    let mut skipped_indent: Option<usize> = None;
+    let mut consumed = 0;
+
+    for line in lines(template) {
+        if line.start < consumed || line.content.is_empty() {
+            continue;
+        }
+        if let Some(indent) = skipped_indent {
+            if line.indent > indent {
+                continue;
+            }
+            skipped_indent = None;
+        }
+
+        let content = line.content;
+        if content.starts_with("-#")
+            || content.starts_with('/')
+            || content.starts_with(':')
+            || content.starts_with("!!!")
+        {
+            blocks.close(&mut extracted, line.indent, None);
+            skipped_indent = Some(line.indent);
+        } else if let Some(code_start) = code_start(content) {
+            consumed = push_code(
+                &mut extracted,
+                &mut blocks,
+                template,
+                &line,
+                line.start + code_start,
+            );
+        } else if content.starts_with(['%', '.']) || is_id_shortcut(content) {
+            blocks.close(&mut extracted, line.indent, None);
+            consumed = push_tag(&mut extracted, &mut blocks, template, &line);
+        } else {
+            blocks.close(&mut extracted, line.indent, None);
+            let text_start = line.start + usize::from(content.starts_with('\\'));
+            push_interpolations(
+                &mut extracted,
+                template,
+                text_start,
+                line_end(template, text_start),
+            );
+        }
+    }
+
+    blocks.close_all(&mut extracted);
+    extracted
+}
+
+// Where the code starts, for lines and tag suffixes like `- code`, `= code` and `!= code`
+fn code_start(content: &str) -> Option<usize> {
+    ["!=", "&=", "-", "=", "~"]
+        .iter()
+        .find(|prefix| content.starts_with(*prefix))
+        .map(|prefix| prefix.len())
+}
+
+// `#main` is a div with an id, while `#{...}` starts plain text
+fn is_id_shortcut(content: &str) -> bool {
+    content.starts_with('#') && !content.starts_with("#{")
+}
+
+// Returns the offset up to which the template is consumed
+fn push_code(
+    extracted: &mut ExtractedRuby,
+    blocks: &mut Blocks,
+    template: &str,
+    line: &Line,
+    start: usize,
+) -> usize {
+    let end = code_end(template, start, &[]);
+    let code = &template[start..end];
+    blocks.close(extracted, line.indent, Some(code));
+    extracted.push_snippet(start, code);
+    blocks.open(line.indent, code);
+    end
+}
+
+fn push_tag(
+    extracted: &mut ExtractedRuby,
+    blocks: &mut Blocks,
+    template: &str,
+    line: &Line,
+) -> usize {
+    let bytes = template.as_bytes();
+    let end_of_line = line_end(template, line.start);
+
+    // %tag.class#id
+    let mut offset = line.start;
+    while offset < end_of_line {
+        let byte = bytes[offset];
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'%' | b'.' | b'#' | b'-' | b'_' | b':') {
+            if byte == b'#' && bytes.get(offset + 1) == Some(&b'{') {
+                break;
+            }
+            offset += 1;
+        } else {
+            break;
+        }
+    }
+
+    // {ruby: "hash"}, [object_reference] and (html="attributes"), possibly spanning lines
+    let mut consumed = end_of_line;
+    while offset < bytes.len() && matches!(bytes[offset], b'{' | b'[' | b'(') {
+        let Some(close) = closing_bracket(template, offset) else {
+            return consumed;
+        };
+        if bytes[offset] != b'(' {
+            extracted.push_snippet(offset, &template[offset..=close]);
+        }
+        offset = close + 1;
+    }
+    let end_of_line = line_end(template, offset);
+    consumed = consumed.max(end_of_line);
+
+    // whitespace removal and self-closing markers
+    while offset < end_of_line && matches!(bytes[offset], b'<' | b'>' | b'/') {
+        offset += 1;
+    }
+
+    let rest = &template[offset..end_of_line];
+    if let Some(code_start) = code_start(rest).filter(|_| !rest.starts_with('-')) {
+        consumed.max(push_code(
+            extracted,
+            blocks,
+            template,
+            line,
+            offset + code_start,
+        ))
+    } else {
+        push_interpolations(extracted, template, offset, end_of_line);
+        consumed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_lines_and_blocks() {
+        let template = "\
+%ul
+  - users.each do |user|
+    %li= user.name
+  - if Feature.enabled?(:admin)
+    = render Admin::Panel.new
+  - else
+    %p Nothing to see
+";
+        assert_eq!(
+            extract_ruby(template).ruby,
+            " users.each do |user|\n user.name\nend\n if Feature.enabled?(:admin)\n render Admin::Panel.new\n else\nend"
+        );
+    }
+
+    #[test]
+    fn attributes_and_interpolation() {
+        let template = "\
+%section.report#summary{ data: { chart: Charts::Line.name },
+  class: css_for(report) }[report]
+  %p.total Total: #{Money.format(report.total)}
+  #footer!= Footer.render
+  .note
+    \\#{escaped} text
+  %a(href=\"/reports\") Reports
+";
+        assert_eq!(
+            extract_ruby(template).ruby,
+            "{ data: { chart: Charts::Line.name },\n  class: css_for(report) }\n[report]\nMoney.format(report.total)\n Footer.render"
+        );
+    }
+
+    #[test]
+    fn comments_and_filters() {
+        let template = "\
+-# Legacy::Widget.render
+/ Commented::Out
+:javascript
+  var user = #{User.first.to_json};
+%p= Current.user
+";
+        assert_eq!(extract_ruby(template).ruby, " Current.user");
+    }
+
+    #[test]
+    fn maps_back_to_the_template() {
+        let template = "%div\n  %p= Admin::User.count\n";
+        let extracted = extract_ruby(template);
+        let ruby_offset = extracted.ruby.find("Admin").unwrap();
+        assert_eq!(
+            extracted.source_map.template_offset(ruby_offset),
+            template.find("Admin").unwrap()
+        );
+    }
+}
@@ -0,0 +1,170 @@
+// Shared by the HAML and Slim extractors. Both languages nest by indentation and close
+// Ruby blocks implicitly, so the extracted Ruby gets an `end` wherever a block is outdented.
+
+use super::source_map::ExtractedRuby;
+
+const BLOCK_KEYWORDS: [&str; 7] = ["if", "unless", "case", "while", "until", "for", "begin"];
+const CONTINUATION_KEYWORDS: [&str; 6] = ["else", "elsif", "when", "in", "rescue", "ensure"];
+
+pub(crate) struct Line<'a> {
+    // offset of the first character after the indentation
+    pub start: usize,
+    pub indent: usize,
+    // without indentation and line break
+    pub content: &'a str,
+}
+
+pub(crate) fn lines(template: &str) -> Vec<Line<'_>> {
+    let mut lines = vec![];
+    let mut line_start = 0;
+    for line in template.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let trimmed = content.trim_start();
+        let indent = content.len() - trimmed.len();
+        lines.push(Line {
+            start: line_start + indent,
+            indent,
+            content: trimmed,
+        });
+        line_start += line.len();
+    }
+    lines
+}
+
+// The blocks opened by code lines, by indentation
+#[derive(Default)]
+pub(crate) struct Blocks {
+    indents: Vec<usize>,
+}
+
+impl Blocks {
+    // Ends the blocks a line at `indent` is outdented from. A code line continuing a block,
+    // like `- else`, keeps the block it is aligned with open.
+    pub fn close(&mut self, extracted: &mut ExtractedRuby, indent: usize, code: Option<&str>) {
+        let continues = code.is_some_and(|code| {
+            first_word(code).is_some_and(|word| CONTINUATION_KEYWORDS.contains(&word))
+        });
+        while let Some(&open_indent) = self.indents.last() {
+            if open_indent < indent || (open_indent == indent && continues) {
+                break;
+            }
+            self.indents.pop();
+            extracted.push_synthetic("end");
+        }
+    }
+
+    pub fn open(&mut self, indent: usize, code: &str) {
+        let code = code.trim();
+        let opens = first_word(code).is_some_and(|word| BLOCK_KEYWORDS.contains(&word))
+            || code.ends_with(" do")
+            || (code.ends_with('|') && code.contains(" do |"));
+        if opens {
+            self.indents.push(indent);
+        }
+    }
+
+    pub fn close_all(&mut self, extracted: &mut ExtractedRuby) {
+        self.close(extracted, 0, None);
+    }
+}
+
+fn first_word(code: &str) -> Option<&str> {
+    code.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .find(|word| !word.is_empty())
+}
+
+// The end of the Ruby code starting at `start`. Code continues on the next line after a
+// trailing comma, or after any of `continuations`.
+pub(crate) fn code_end(template: &str, start: usize, continuations: &[char]) -> usize {
+    let mut end = line_end(template, start);
+    while end < template.len()
+        && template[start..end]
+            .trim_end()
+            .ends_with(|c| c == ',' || continuations.contains(&c))
+    {
+        end = line_end(template, end + 1);
+    }
+    end
+}
+
+pub(crate) fn line_end(template: &str, offset: usize) -> usize {
+    template[offset..]
+        .find('\n')
+        .map_or(template.len(), |index| offset + index)
+}
+
+// The offset of the bracket closing the one at `open`, skipping over quoted strings
+pub(crate) fn closing_bracket(template: &str, open: usize) -> Option<usize> {
+    let bytes = template.as_bytes();
+    let mut depth = 0;
+    let mut quote: Option<u8> = None;
+    let mut index = open;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        match quote {
+            Some(_) if byte == b'\\' => index += 1,
+            Some(q) if byte == q => quote = None,
+            Some(_) => {}
+            None => match byte {
+                b'"' | b'\'' => quote = Some(byte),
+                b'{' | b'[' | b'(' => depth += 1,
+                b'}' | b']' | b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(index);
+                    }
+                }
+                _ => {}
+            },
+        }
+        index += 1;
+    }
+    None
+}
+
+// Extracts the code of each `#{...}` in the text between `start` and `end`
+pub(crate) fn push_interpolations(
+    extracted: &mut ExtractedRuby,
+    template: &str,
+    start: usize,
+    end: usize,
+) {
+    let mut offset = start;
+    while let Some(index) = template[offset..end].find("#{") {
+        let open = offset + index + 1;
+        let escaped = open >= 2 && template.as_bytes()[open - 2] == b'\\';
+        let Some(close) = closing_bracket(template, open).filter(|&close| close < end) else {
+            return;
+        };
+        if !escaped {
+            extracted.push_snippet(open + 1, &template[open + 1..close]);
+        }
+        offset = close + 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolations() {
+        let text = r#"Hello #{user.name}, \#{not_code} and #{Greeting.for(user) { "}" }}!"#;
+        let mut extracted = ExtractedRuby::default();
+        push_interpolations(&mut extracted, text, 0, text.len());
+        assert_eq!(extracted.ruby, "user.name\nGreeting.for(user) { \"}\" }");
+    }
+
+    #[test]
+    fn blocks() {
+        let mut extracted = ExtractedRuby::default();
+        let mut blocks = Blocks::default();
+        blocks.open(0, "if admin?");
+        blocks.close(&mut extracted, 2, Some("Audit.log"));
+        blocks.close(&mut extracted, 0, Some("else"));
+        assert_eq!(extracted.ruby, "");
+        blocks.open(2, "users.each do |user|");
+        blocks.close_all(&mut extracted);
+        assert_eq!(extracted.ruby, "end\nend");
+    }
+}
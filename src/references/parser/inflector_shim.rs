@@ -1,52 +1,24 @@
-use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
 
 use regex::Regex;
-use ruby_inflector::case::{
-    to_case_camel_like, to_class_case as to_class_case_original, CamelOptions,
-};
-
-// This is a list of plural to singular words that are not handled by the inflector
-// The plural words are
-const CLASS_CASE_TO_SINGULAR: [(&str, &str); 7] = [
-    ("Statuse$", "Status"),
-    ("Statu$", "Status"),
-    ("Statuss$", "Status"),
-    ("Censuse$", "Census"),
-    ("Leafe", "Leave"),
-    ("Lefe", "Leave"),
-    ("Daum", "Datum"),
-];
-
-// See https://github.com/whatisinternet/Inflector/pull/87
-// Note that as of the PR that adds this comment, we are now using https://github.com/alexevanczuk/ruby_inflector,
-// so that we have an easier time making this inflector more specific to ruby applications (for now)
-pub fn to_class_case(s: &str, should_singularize: bool, acronyms: &HashSet<String>) -> String {
-    let options = CamelOptions {
-        new_word: true,
-        last_char: ' ',
-        first_word: false,
-        injectable_char: ' ',
-        has_seperator: false,
-        inverted: false,
-    };
-
-    let mut class_name = if should_singularize {
-        to_class_case_original(s, acronyms)
-    } else {
-        to_case_camel_like(s, options, acronyms)
-    };
 
-    CLASS_CASE_TO_SINGULAR
-        .into_iter()
-        .for_each(|(plural, singular)| {
-            let re = Regex::new(plural).unwrap();
-            class_name = re.replace_all(&class_name, singular).to_string();
-        });
+use crate::references::inflections::Inflections;
+
+static LEADING_WORD_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new("^[a-z\\d]*").unwrap());
+static UNDERSCORE_OR_SLASH_WORD_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("(?:_|(/))([a-z\\d]*)").unwrap());
 
-    class_name
+// Emulates `name.to_s.singularize.camelize`, which is how ActiveRecord derives the class name
+// of an association declared with a symbol.
+pub fn to_class_case(s: &str, should_singularize: bool, inflections: &Inflections) -> String {
+    if should_singularize {
+        camelize(&inflections.singularize(s), inflections)
+    } else {
+        camelize(s, inflections)
+    }
 }
 
-pub fn camelize(s: &str, acronyms: &HashSet<String>) -> String {
+pub fn camelize(s: &str, inflections: &Inflections) -> String {
     // Meant to emulate https://github.com/rails/rails/blob/e88857bbb9d4e1dd64555c34541301870de4a45b/activesupport/lib/active_support/inflector/methods.rb#L69
     //
     // def camelize(term, uppercase_first_letter = true)
@@ -65,36 +37,26 @@ pub fn camelize(s: &str, acronyms: &HashSet<String>) -> String {
     //   string
     // end
 
-    let lowercase_acronyms_to_originals = acronyms
-        .iter()
-        .map(|acronym| (acronym.to_lowercase(), acronym))
-        .collect::<HashMap<String, &String>>();
-
     let mut new_string = s.to_string();
     // Replace the beginning of the word, matched with lowercase letters, with either a matching inflection or a capitalized version of the word
-    let re = Regex::new("^[a-z\\d]*").unwrap();
-    new_string = re
+    new_string = LEADING_WORD_REGEX
         .replace(&new_string, |caps: &regex::Captures| {
             let word = caps.get(0).unwrap().as_str();
-            if lowercase_acronyms_to_originals.contains_key(word) {
-                lowercase_acronyms_to_originals[word].to_string()
-            } else {
-                capitalize(word)
+            match inflections.acronyms.get(word) {
+                Some(acronym) => acronym.to_owned(),
+                None => capitalize(word),
             }
         })
         .to_mut()
         .to_string();
 
-    let re = Regex::new("(?:_|(/))([a-z\\d]*)").unwrap();
-
-    new_string = re
+    new_string = UNDERSCORE_OR_SLASH_WORD_REGEX
         .replace_all(&new_string, |caps: &regex::Captures| {
             let matched_slash = caps.get(1);
             let word = caps.get(2).unwrap().as_str();
-            let capitalized_word = if lowercase_acronyms_to_originals.contains_key(word) {
-                lowercase_acronyms_to_originals[word].to_string()
-            } else {
-                capitalize(word)
+            let capitalized_word = match inflections.acronyms.get(word) {
+                Some(acronym) => acronym.to_owned(),
+                None => capitalize(word),
             };
 
             if matched_slash.is_some() {
@@ -125,24 +87,24 @@ mod tests {
 
     #[test]
     fn test_trivial() {
-        let actual = to_class_case("my_string", false, &HashSet::new());
+        let actual = to_class_case("my_string", false, &Inflections::default());
         let expected = "MyString";
         assert_eq!(expected, actual);
     }
 
     #[test]
     fn test_digits() {
-        let actual = to_class_case("my_string_401k_thing", false, &HashSet::new());
+        let actual = to_class_case("my_string_401k_thing", false, &Inflections::default());
         let expected = "MyString401kThing";
         assert_eq!(expected, actual);
     }
 
     #[test]
     fn fn_test_camelizing_case_retained() {
-        let mut acronyms = HashSet::new();
-        acronyms.insert(String::from("FacTory"));
+        let mut inflections = Inflections::default();
+        inflections.acronym("FacTory");
 
-        let actual = camelize("my_factory", &acronyms);
+        let actual = camelize("my_factory", &inflections);
         let expected = "MyFacTory";
         assert_eq!(expected, actual);
     }
@@ -151,20 +113,22 @@ mod tests {
     fn test_to_class_case() {
         let tests = vec![
             ("my_string", false, "MyString"),
-            ("censuses", true, "Census"),
-            ("lefe", true, "Leave"),
             ("leaves", false, "Leaves"),
-            ("daum", true, "Datum"),
-            ("statuss", false, "Status"),
             ("statuss_foo", false, "StatussFoo"),
-            ("statuss", true, "Status"),
             ("statuses", true, "Status"),
             ("statuses_foo", true, "StatusesFoo"),
-            ("censuse", true, "Census"),
+            ("status", true, "Status"),
+            ("data", true, "Datum"),
+            ("addresses", true, "Address"),
+            ("line_items", true, "LineItem"),
+            ("people", true, "Person"),
+            ("news", true, "News"),
+            // Like Rails, census needs an application inflection rule
+            ("censuses", true, "Censuse"),
         ];
 
         for (input, should_singularize, expected) in tests {
-            let actual = to_class_case(input, should_singularize, &HashSet::new());
+            let actual = to_class_case(input, should_singularize, &Inflections::default());
             assert_eq!(
                 expected, actual,
                 "Failed for input: {}, and singularize: {}",
@@ -172,4 +136,24 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_to_class_case_with_app_inflections() {
+        let mut inflections = Inflections::default();
+        inflections.irregular("census", "censuses");
+        inflections.uncountable("staff");
+        inflections.acronym("API");
+
+        let tests = vec![
+            ("censuses", true, "Census"),
+            ("staff", true, "Staff"),
+            ("api_clients", true, "APIClient"),
+            ("companies", true, "Company"),
+        ];
+
+        for (input, should_singularize, expected) in tests {
+            let actual = to_class_case(input, should_singularize, &inflections);
+            assert_eq!(expected, actual, "Failed for input: {}", input);
+        }
+    }
 }
@@ -0,0 +1,71 @@
+use line_col::LineColLookup;
+
+use super::source_map::SourceMap;
+
+// Looks up the line and column of a byte offset. Columns are counted in bytes, as the
+// parser reports locations, or in UTF-16 code units, as editors speaking LSP expect them.
+// For Ruby extracted from a template, offsets in the parsed Ruby are looked up in the template.
+pub(crate) struct LineIndex<'a> {
+    contents: &'a str,
+    lookup: LineColLookup<'a>,
+    source_map: Option<&'a SourceMap>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(contents: &'a str) -> Self {
+        LineIndex {
+            contents,
+            lookup: LineColLookup::new(contents),
+            source_map: None,
+        }
+    }
+
+    pub fn with_source_map(template: &'a str, source_map: &'a SourceMap) -> Self {
+        LineIndex {
+            source_map: Some(source_map),
+            ..LineIndex::new(template)
+        }
+    }
+
+    // The byte offset in the file of a byte offset in the parsed Ruby
+    pub fn source_offset(&self, byte_offset: usize) -> usize {
+        self.source_map.map_or(byte_offset, |source_map| {
+            source_map.template_offset(byte_offset)
+        })
+    }
+
+    // 1-based line and byte column
+    pub fn get(&self, byte_offset: usize) -> (usize, usize) {
+        self.lookup.get(self.source_offset(byte_offset))
+    }
+
+    // 0-based column in UTF-16 code units
+    pub fn utf16_column(&self, byte_offset: usize) -> usize {
+        let source_offset = self.source_offset(byte_offset);
+        let (_, column) = self.lookup.get(source_offset);
+        let line_start = source_offset - (column - 1);
+        self.contents
+            .get(line_start..source_offset)
+            .map_or(column - 1, |prefix| prefix.encode_utf16().count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf16_column() {
+        // "é" is two bytes and one UTF-16 code unit, "😀" is four bytes and two
+        let contents = "name = \"é😀\"\nFoo";
+        let line_index = LineIndex::new(contents);
+
+        let closing_quote = contents.rfind('"').unwrap();
+        assert_eq!(line_index.get(closing_quote), (1, 15));
+        assert_eq!(line_index.utf16_column(closing_quote), 11);
+
+        let foo = contents.find("Foo").unwrap();
+        assert_eq!(line_index.get(foo), (2, 1));
+        assert_eq!(line_index.utf16_column(foo), 0);
+    }
+}
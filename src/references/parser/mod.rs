@@ -1,27 +1,42 @@
-use std::path::PathBuf;
+use std::{fmt, path::PathBuf};
 
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 
-use crate::references::{cache::CacheResult, configuration};
+use crate::references::{
+    cache::CacheResult, configuration, error::ReferencesError, reference::ReferenceKind,
+};
 
 use self::processor::process_file;
 
 pub(crate) mod collector;
+pub(crate) mod erb;
+pub(crate) mod haml;
+pub(crate) mod indented_template;
 pub(crate) mod inflector_shim;
+pub(crate) mod line_index;
 pub(crate) mod namespace_calculator;
 pub(crate) mod processor;
 pub(crate) mod self_reference_filterer;
+pub(crate) mod slim;
+pub(crate) mod source_map;
 
+// Rows are 1-based. start_col is the 0-based byte column, as packwerk reports it, while
+// end_col is the 1-based byte column just past the end. Byte offsets and UTF-16 columns
+// are 0-based and exclusive at the end.
 #[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
 pub struct Range {
     pub start_row: usize,
     pub start_col: usize,
     pub end_row: usize,
     pub end_col: usize,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start_utf16_col: usize,
+    pub end_utf16_col: usize,
 }
 
-#[derive(Debug, PartialEq, Clone, Eq)]
+#[derive(Debug, PartialEq, Clone, Eq, Serialize, Deserialize)]
 pub struct ParsedDefinition {
     pub fully_qualified_name: String,
     pub location: Range,
@@ -31,6 +46,118 @@ pub struct ParsedDefinition {
 pub struct ProcessedFile {
     pub absolute_path: PathBuf,
     pub unresolved_references: Vec<UnresolvedReference>,
+    // Classes, modules and constant assignments defined in the file
+    pub definitions: Vec<ParsedDefinition>,
+    // Errors and warnings reported by the parser. References are still collected from the
+    // partial AST when the parser recovers from an error.
+    pub diagnostics: Vec<ParseDiagnostic>,
+    pub skipped_constructs: Vec<SkippedConstruct>,
+    pub mixins: Vec<ParsedMixin>,
+    pub superclasses: Vec<ParsedSuperclass>,
+}
+
+/// `class Foo < Bar`, before `superclass_name` is resolved. `class_name` is fully qualified,
+/// and `namespace_path` is the one `superclass_name` is looked up from.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ParsedSuperclass {
+    pub class_name: String,
+    pub superclass_name: String,
+    pub namespace_path: Vec<String>,
+    pub location: Range,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum MixinKind {
+    Include,
+    Extend,
+    Prepend,
+}
+
+/// `include Foo`, `extend Foo` or `prepend Foo` in the body of a class or module, before
+/// `module_name` is resolved. `class_name` is fully qualified; a mixin outside of any class
+/// or module goes into `::Object`.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ParsedMixin {
+    pub class_name: String,
+    pub module_name: String,
+    pub namespace_path: Vec<String>,
+    pub kind: MixinKind,
+    pub location: Range,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SkipReason {
+    // e.g. `class self::Foo` or `class klass::Bar`, neither the class nor its body is visited
+    DynamicClassName,
+    // e.g. `module mod::Helpers`, neither the module nor its body is visited
+    DynamicModuleName,
+    // e.g. `record.class::LIMIT` or `klass::Bar = 1`, the scope is only known at runtime
+    DynamicConstantScope,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::DynamicClassName => write!(f, "class name is not a constant"),
+            SkipReason::DynamicModuleName => write!(f, "module name is not a constant"),
+            SkipReason::DynamicConstantScope => write!(f, "constant scope is not a constant"),
+        }
+    }
+}
+
+/// Metaprogramming the collector cannot follow statically. Constants it references or
+/// defines are missing from the results.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct SkippedConstruct {
+    pub file: PathBuf,
+    pub location: Range,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DiagnosticSeverity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for DiagnosticSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticSeverity::Warning => write!(f, "warning"),
+            DiagnosticSeverity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A syntax error or warning reported by the parser. `line` is 1-based and `column` is
+/// 0-based, like the locations of references.
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ParseDiagnostic {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    pub severity: DiagnosticSeverity,
+}
+
+impl ParseDiagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == DiagnosticSeverity::Error
+    }
+}
+
+impl fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}: {}",
+            self.file.display(),
+            self.line,
+            self.column,
+            self.severity,
+            self.message
+        )
+    }
 }
 
 #[derive(Debug, PartialEq, Default, Eq, Clone, PartialOrd, Ord, Serialize, Deserialize)]
@@ -44,12 +171,13 @@ pub struct UnresolvedReference {
     pub name: String,
     pub namespace_path: Vec<String>,
     pub location: Range,
+    pub kind: ReferenceKind,
 }
 
 pub fn parse(configuration: &configuration::Configuration) -> anyhow::Result<Vec<ProcessedFile>> {
     let cache = configuration.get_cache();
 
-    configuration
+    let processed_files = configuration
         .included_files
         .par_iter()
         .map(|path| -> anyhow::Result<ProcessedFile> {
@@ -63,7 +191,25 @@ pub fn parse(configuration: &configuration::Configuration) -> anyhow::Result<Vec
                 Err(e) => Err(e),
             }
         })
-        .collect()
+        .collect::<anyhow::Result<Vec<ProcessedFile>>>()?;
+
+    if configuration.strict_parse {
+        let mut errors = processed_files
+            .iter()
+            .flat_map(|processed_file| processed_file.diagnostics.iter())
+            .filter(|diagnostic| diagnostic.is_error())
+            .cloned()
+            .collect::<Vec<ParseDiagnostic>>();
+        if !errors.is_empty() {
+            errors.sort();
+            return Err(ReferencesError::ParseFailed {
+                diagnostics: errors,
+            }
+            .into());
+        }
+    }
+
+    Ok(processed_files)
 }
 
 #[cfg(test)]
@@ -89,19 +235,14 @@ mod tests {
             .map(|pfile| pfile.absolute_path.to_str().unwrap())
             .collect();
 
-        assert_eq!(processed_paths.len(), 28);
-        assert_eq!(
-            processed_paths.contains(
-                &PathBuf::from(
-                    "tests/fixtures/small-app/app/controllers/application_controller.rb"
-                )
+        assert_eq!(processed_paths.len(), 31);
+        assert!(processed_paths.contains(
+            &PathBuf::from("tests/fixtures/small-app/app/controllers/application_controller.rb")
                 .canonicalize()
                 .unwrap()
                 .to_str()
                 .unwrap()
-            ),
-            true
-        );
+        ));
         Ok(())
     }
     #[test]
@@ -113,6 +254,7 @@ mod tests {
 
         let cached_file = CachedFile {
             cache_dir: PathBuf::from(&cache_dir),
+            settings_digest: configuration.cache_settings_digest(),
         };
         let cache_result = cached_file.get(&file_path);
         assert!(cache_result.is_ok());
@@ -122,16 +264,20 @@ mod tests {
                 let cache = configuration.get_cache();
                 cache.write(&empty_cache_entry, &processed_file)?;
             }
-            _ => {
-                assert!(false)
-            }
+            _ => panic!("expected a cache miss"),
         }
         let cache_result = cached_file.get(&file_path);
         assert!(cache_result.is_ok());
         match cache_result.unwrap() {
-            CacheResult::Miss(_) => assert!(false),
+            CacheResult::Miss(_) => panic!("expected a cache hit"),
             CacheResult::Processed(processed_file) => {
                 assert_eq!(processed_file.absolute_path, file_path);
+                let definitions = processed_file
+                    .definitions
+                    .iter()
+                    .map(|d| d.fully_qualified_name.as_str())
+                    .collect::<Vec<&str>>();
+                assert_eq!(definitions, vec!["::Company", "::Company::Widget"]);
             }
         }
 
@@ -3,40 +3,68 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use anyhow::Context;
-use lib_ruby_parser::{traverse::visitor::Visitor, Node, Parser, ParserOptions};
-use line_col::LineColLookup;
-use regex::Regex;
+use lib_ruby_parser::{traverse::visitor::Visitor, ErrorLevel, Node, Parser, ParserOptions};
 
-use crate::references::{configuration, parser::collector::ReferenceCollector};
+use crate::references::{
+    configuration, error::ReferencesError, parser::collector::ReferenceCollector,
+    reference::ReferenceKind,
+};
 
-use super::{self_reference_filterer, ProcessedFile};
+use super::{
+    erb, haml, line_index::LineIndex, self_reference_filterer, slim, source_map::ExtractedRuby,
+    DiagnosticSeverity, ParseDiagnostic, ProcessedFile,
+};
 
 pub fn process_file(
     path: &PathBuf,
     configuration: &configuration::Configuration,
 ) -> anyhow::Result<ProcessedFile> {
-    let contents = match get_file_type(path, configuration) {
-        Some(SupportedFileType::Ruby) => file_read_contents(path)?,
+    match get_file_type(path, configuration) {
+        Some(SupportedFileType::Ruby) => {
+            process_from_contents(file_read_contents(path)?, path, configuration)
+        }
         Some(SupportedFileType::Erb) => {
-            let c = file_read_contents(path)?;
-            convert_erb_to_ruby_without_sourcemaps(c)
+            process_template(path, configuration, erb::extract_ruby, ReferenceKind::Erb)
         }
-        None => {
-            return Ok(ProcessedFile {
-                absolute_path: path.to_path_buf(),
-                ..Default::default()
-            })
+        Some(SupportedFileType::Haml) => {
+            process_template(path, configuration, haml::extract_ruby, ReferenceKind::Haml)
         }
-    };
+        Some(SupportedFileType::Slim) => {
+            process_template(path, configuration, slim::extract_ruby, ReferenceKind::Slim)
+        }
+        None => Ok(ProcessedFile {
+            absolute_path: path.to_path_buf(),
+            ..Default::default()
+        }),
+    }
+}
 
-    process_from_contents(contents, path, configuration)
+// Parses the Ruby extracted from a template, reporting locations in the template
+fn process_template(
+    path: &PathBuf,
+    configuration: &configuration::Configuration,
+    extract_ruby: fn(&str) -> ExtractedRuby,
+    kind: ReferenceKind,
+) -> anyhow::Result<ProcessedFile> {
+    let template = file_read_contents(path)?;
+    let extracted = extract_ruby(&template);
+    let line_index = LineIndex::with_source_map(&template, &extracted.source_map);
+    let mut processed_file = process_from_ruby(extracted.ruby, line_index, path, configuration)?;
+    // More specific kinds, e.g. Mixin or TypeOnly, are kept
+    for reference in processed_file.unresolved_references.iter_mut() {
+        if reference.kind == ReferenceKind::Constant {
+            reference.kind = kind;
+        }
+    }
+    Ok(processed_file)
 }
 
 #[derive(PartialEq, Debug)]
 enum SupportedFileType {
     Ruby,
     Erb,
+    Haml,
+    Slim,
 }
 
 fn get_file_type(
@@ -45,14 +73,20 @@ fn get_file_type(
 ) -> Option<SupportedFileType> {
     let extension = path.extension();
 
-    if extension.map_or(false, |ext| ext == "erb") {
+    if extension.is_some_and(|ext| ext == "erb") {
         return Some(SupportedFileType::Erb);
     }
+    if extension.is_some_and(|ext| ext == "haml") {
+        return Some(SupportedFileType::Haml);
+    }
+    if extension.is_some_and(|ext| ext == "slim") {
+        return Some(SupportedFileType::Slim);
+    }
 
     let is_ruby_file = configuration
         .ruby_extensions
         .iter()
-        .any(|ext| extension.map_or(false, |e| e == *ext))
+        .any(|ext| extension.is_some_and(|e| e == *ext))
         || configuration
             .ruby_special_files
             .iter()
@@ -65,24 +99,14 @@ fn get_file_type(
     }
 }
 
-const ERB_REGEX: &str = r"(?s)<%=?-?\s*(.*?)\s*-?%>";
-
-fn convert_erb_to_ruby_without_sourcemaps(contents: String) -> String {
-    let regex = Regex::new(ERB_REGEX).unwrap();
-
-    let extracted_contents: Vec<&str> = regex
-        .captures_iter(&contents)
-        .map(|capture| capture.get(1).unwrap().as_str())
-        .collect();
-
-    extracted_contents.join("\n")
-}
-
 fn file_read_contents(path: &PathBuf) -> anyhow::Result<String> {
-    fs::read_to_string(path).context(format!(
-        "Failed to read contents of {}",
-        path.to_string_lossy()
-    ))
+    fs::read_to_string(path).map_err(|source| {
+        ReferencesError::UnreadableFile {
+            path: path.to_owned(),
+            source,
+        }
+        .into()
+    })
 }
 
 fn process_from_contents(
@@ -90,22 +114,42 @@ fn process_from_contents(
     path: &PathBuf,
     configuration: &configuration::Configuration,
 ) -> anyhow::Result<ProcessedFile> {
-    let lookup = LineColLookup::new(&contents);
+    let line_index = LineIndex::new(&contents);
+    process_from_ruby(contents.clone(), line_index, path, configuration)
+}
 
-    let ast = match build_ast(contents.clone()) {
+// `lookup` reports locations in the file, which differ from those in `ruby` for templates
+fn process_from_ruby(
+    ruby: String,
+    lookup: LineIndex,
+    path: &PathBuf,
+    configuration: &configuration::Configuration,
+) -> anyhow::Result<ProcessedFile> {
+    let (ast, diagnostics) = build_ast(ruby, path, &lookup);
+    let ast = match ast {
         Some(ast) => ast,
         None => {
             return Ok(ProcessedFile {
                 absolute_path: path.clone(),
-                unresolved_references: vec![],
+                diagnostics,
+                ..Default::default()
             })
         }
     };
 
-    let mut collector = ReferenceCollector::new(lookup, configuration.custom_associations.clone());
+    let mut collector = ReferenceCollector::new(
+        path,
+        lookup,
+        configuration.custom_associations.clone(),
+        &configuration.inflections,
+    );
 
     collector.visit(&ast);
 
+    let definitions = collector.definitions.clone();
+    let skipped_constructs = collector.skipped_constructs.clone();
+    let mixins = collector.mixins.clone();
+    let superclasses = collector.parsed_superclasses.clone();
     let unresolved_references = if configuration.include_reference_is_definition {
         collector.references
     } else {
@@ -115,17 +159,47 @@ fn process_from_contents(
     Ok(ProcessedFile {
         absolute_path: path.to_owned(),
         unresolved_references,
+        definitions,
+        diagnostics,
+        skipped_constructs,
+        mixins,
+        superclasses,
     })
 }
 
-fn build_ast(contents: String) -> Option<Box<Node>> {
+// The AST is Some whenever the parser could recover from its errors, and then holds
+// every node it managed to build
+fn build_ast(
+    contents: String,
+    path: &Path,
+    lookup: &LineIndex,
+) -> (Option<Box<Node>>, Vec<ParseDiagnostic>) {
     let options = ParserOptions {
         buffer_name: "".to_string(),
         ..Default::default()
     };
     let parser = Parser::new(contents, options);
     let parse_result = parser.do_parse();
-    parse_result.ast
+
+    let diagnostics = parse_result
+        .diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let (line, column) = lookup.get(diagnostic.loc.begin);
+            ParseDiagnostic {
+                file: path.to_path_buf(),
+                line,
+                column: column - 1,
+                message: diagnostic.render_message(),
+                severity: match diagnostic.level {
+                    ErrorLevel::Warning => DiagnosticSeverity::Warning,
+                    ErrorLevel::Error => DiagnosticSeverity::Error,
+                },
+            }
+        })
+        .collect();
+
+    (parse_result.ast, diagnostics)
 }
 
 #[cfg(test)]
@@ -203,6 +277,316 @@ mod tests {
         );
         assert_eq!(processed_file.unresolved_references.len(), 1);
         assert_eq!(processed_file.unresolved_references[0].name, "Admin::User");
+        assert_eq!(
+            processed_file.unresolved_references[0].kind,
+            ReferenceKind::Erb
+        );
+        // `    <% @user = Admin::User.new %>` on line 13 of the template
+        let location = &processed_file.unresolved_references[0].location;
+        assert_eq!((location.start_row, location.start_col), (13, 15));
+        assert_eq!((location.end_row, location.end_col), (13, 27));
+    }
+
+    fn reference_names_and_lines(processed_file: &ProcessedFile) -> Vec<(&str, usize)> {
+        processed_file
+            .unresolved_references
+            .iter()
+            .map(|r| (r.name.as_str(), r.location.start_row))
+            .collect()
+    }
+
+    #[test]
+    fn template_keeps_specific_kinds() {
+        let processed_file = process(
+            "tests/fixtures/app_with_templates/app/views/orders/_summary.html.erb",
+            false,
+        );
+        let kinds = processed_file
+            .unresolved_references
+            .iter()
+            .map(|r| (r.name.as_str(), r.kind))
+            .collect::<Vec<(&str, ReferenceKind)>>();
+        assert_eq!(
+            kinds,
+            vec![
+                ("OrdersHelper", ReferenceKind::Mixin),
+                ("Order", ReferenceKind::Erb),
+            ]
+        );
+    }
+
+    #[test]
+    fn process_haml_file() {
+        let processed_file = process(
+            "tests/fixtures/small-app/app/views/reports/show.html.haml",
+            false,
+        );
+        assert!(processed_file.diagnostics.is_empty());
+        assert_eq!(
+            reference_names_and_lines(&processed_file),
+            vec![
+                ("Charts::Line", 1),
+                ("Feature", 2),
+                ("Exports::Csv", 3),
+                ("Account", 5),
+                ("Formatter", 8),
+            ]
+        );
+        assert!(processed_file
+            .unresolved_references
+            .iter()
+            .all(|r| r.kind == ReferenceKind::Haml));
+        // `      %li= Formatter.format(row)`
+        assert_eq!(
+            processed_file.unresolved_references[4].location.start_col,
+            11
+        );
+    }
+
+    #[test]
+    fn process_slim_file() {
+        let processed_file = process(
+            "tests/fixtures/small-app/app/views/reports/index.html.slim",
+            false,
+        );
+        assert!(processed_file.diagnostics.is_empty());
+        assert_eq!(
+            reference_names_and_lines(&processed_file),
+            vec![
+                ("Charts::Line", 1),
+                ("Feature", 2),
+                ("Exports::Csv", 3),
+                ("Account", 5),
+                ("Formatter", 8),
+            ]
+        );
+        assert!(processed_file
+            .unresolved_references
+            .iter()
+            .all(|r| r.kind == ReferenceKind::Slim));
+        // `      li = Formatter.format(report)`
+        assert_eq!(
+            processed_file.unresolved_references[4].location.start_col,
+            11
+        );
+    }
+
+    #[test]
+    fn reference_kinds() -> anyhow::Result<()> {
+        let processed_file = process_from_contents(
+            String::from(
+                "\
+class Order < ApplicationRecord
+  include Auditable
+  extend Searchable::ClassMethods
+  belongs_to :customer
+  LIMIT = Settings::LIMIT
+end
+",
+            ),
+            &PathBuf::from("app/models/order.rb"),
+            &Configuration::default(),
+        )?;
+        let kinds = processed_file
+            .unresolved_references
+            .iter()
+            .map(|r| (r.name.as_str(), r.kind))
+            .collect::<Vec<(&str, ReferenceKind)>>();
+        assert_eq!(
+            kinds,
+            vec![
+                ("ApplicationRecord", ReferenceKind::Superclass),
+                ("::Order", ReferenceKind::Definition),
+                ("Auditable", ReferenceKind::Mixin),
+                ("Searchable::ClassMethods", ReferenceKind::Mixin),
+                ("Customer", ReferenceKind::Association),
+                ("Settings::LIMIT", ReferenceKind::Constant),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn association_options() -> anyhow::Result<()> {
+        let configuration = Configuration {
+            custom_associations: vec![String::from("has_many_api_clients")],
+            ..Default::default()
+        };
+        let processed_file = process_from_contents(
+            String::from(
+                "\
+class Order < ApplicationRecord
+  belongs_to :address
+  has_many :line_items
+  belongs_to :buyer, class_name: \"::Customer\"
+  has_one :invoice, class_name: :\"Billing::Invoice\"
+  has_many :refunds, class_name: Payments::Refund.name
+  has_many :credits, class_name: StoreCredit.to_s
+  has_many :products, through: :line_items
+  has_many :sellers, through: :products, source: :merchants
+  has_many :tags, through: :taggings, source: :taggable, source_type: \"Label\"
+  belongs_to :owner, polymorphic: true
+  has_many :notes, class_name: \"#{prefix}Note\"
+  has_many_api_clients :api_clients
+end
+",
+            ),
+            &PathBuf::from("app/models/order.rb"),
+            &configuration,
+        )?;
+        let associations = processed_file
+            .unresolved_references
+            .iter()
+            .filter(|r| r.kind == ReferenceKind::Association)
+            .map(|r| (r.name.as_str(), r.location.start_row))
+            .collect::<Vec<(&str, usize)>>();
+        assert_eq!(
+            associations,
+            vec![
+                ("Address", 2),
+                ("LineItem", 3),
+                ("::Customer", 4),
+                ("Billing::Invoice", 5),
+                ("Payments::Refund", 6),
+                ("StoreCredit", 7),
+                ("Product", 8),
+                ("Merchant", 9),
+                ("Label", 10),
+                ("ApiClient", 13),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn string_constants() -> anyhow::Result<()> {
+        let processed_file = process_from_contents(
+            String::from(
+                "\
+module Billing
+  class Charge
+    \"Billing::Invoice\".constantize
+    \"::Refund\".safe_constantize
+    Object.const_get(\"Customer\")
+    const_get(:Tax)
+    Payments.const_get(:Gateway)
+    \"#{kind}Charge\".constantize
+    \"receipt\".constantize
+    klass.const_get(:Line)
+  end
+end
+",
+            ),
+            &PathBuf::from("app/models/billing/charge.rb"),
+            &Configuration::default(),
+        )?;
+        let dynamic = processed_file
+            .unresolved_references
+            .iter()
+            .filter(|r| r.kind == ReferenceKind::Dynamic)
+            .map(|r| {
+                (
+                    r.name.as_str(),
+                    r.namespace_path.join("::"),
+                    r.location.start_col,
+                )
+            })
+            .collect::<Vec<(&str, String, usize)>>();
+        assert_eq!(
+            dynamic,
+            vec![
+                ("::Billing::Invoice", String::new(), 4),
+                ("::Refund", String::new(), 4),
+                ("::Customer", String::new(), 21),
+                ("Tax", String::from("Billing::Charge"), 14),
+                ("Payments::Gateway", String::from("Billing::Charge"), 23),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn sorbet_type_only_references() -> anyhow::Result<()> {
+        let processed_file = process_from_contents(
+            String::from(
+                "\
+class Invoice < T::Struct
+  extend T::Sig
+  Total = T.type_alias { T.any(Money, Credit) }
+
+  prop :customer, Customer, default: Customer.guest
+  const :lines, T::Array[Line]
+
+  sig { params(tax: Tax).void }
+  def apply(tax)
+    rate = T.let(Rates.current, Rate)
+    T.cast(rate, ::Percentage)
+    Ledger.record(rate)
+  end
+end
+",
+            ),
+            &PathBuf::from("app/models/invoice.rb"),
+            &Configuration::default(),
+        )?;
+        let kinds = processed_file
+            .unresolved_references
+            .iter()
+            .filter(|r| !r.name.starts_with('T'))
+            .map(|r| (r.name.as_str(), r.kind))
+            .collect::<Vec<(&str, ReferenceKind)>>();
+        assert_eq!(
+            kinds,
+            vec![
+                ("::Invoice", ReferenceKind::Definition),
+                ("Money", ReferenceKind::TypeOnly),
+                ("Credit", ReferenceKind::TypeOnly),
+                ("Customer", ReferenceKind::TypeOnly),
+                ("Customer", ReferenceKind::Constant),
+                ("Line", ReferenceKind::TypeOnly),
+                ("Rates", ReferenceKind::Constant),
+                ("Rate", ReferenceKind::TypeOnly),
+                ("::Percentage", ReferenceKind::TypeOnly),
+                ("Ledger", ReferenceKind::Constant),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unrecoverable_syntax_error() -> anyhow::Result<()> {
+        let path = PathBuf::from("app/models/broken.rb");
+        let processed_file = process_from_contents(
+            String::from("class Foo\n  Bar\n  def x = ;\nend\n"),
+            &path,
+            &Configuration::default(),
+        )?;
+        assert!(processed_file.unresolved_references.is_empty());
+        assert_eq!(
+            processed_file.diagnostics[0],
+            ParseDiagnostic {
+                file: path,
+                line: 3,
+                column: 10,
+                message: String::from("unexpected tSEMI"),
+                severity: DiagnosticSeverity::Error,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn multibyte_ranges() -> anyhow::Result<()> {
+        let processed_file = process_from_contents(
+            String::from("label = \"Café 😀\"; Menu::Item\n"),
+            &PathBuf::from("app/models/menu.rb"),
+            &Configuration::default(),
+        )?;
+        let location = &processed_file.unresolved_references[0].location;
+        assert_eq!((location.start_col, location.end_col), (22, 33));
+        assert_eq!((location.start_byte, location.end_byte), (22, 32));
+        assert_eq!((location.start_utf16_col, location.end_utf16_col), (19, 29));
+        Ok(())
     }
 
     #[test]
@@ -232,6 +616,14 @@ mod tests {
                 "tests/fixtures/small-app/app/views/layouts/application.html.erb",
                 Some(SupportedFileType::Erb),
             ),
+            (
+                "tests/fixtures/small-app/app/views/reports/show.html.haml",
+                Some(SupportedFileType::Haml),
+            ),
+            (
+                "tests/fixtures/small-app/app/views/reports/index.html.slim",
+                Some(SupportedFileType::Slim),
+            ),
             (
                 "tests/fixtures/small-app/Gemfile",
                 Some(SupportedFileType::Ruby),
@@ -0,0 +1,283 @@
+use super::{
+    indented_template::{
+        closing_bracket, code_end, line_end, lines, push_interpolations, Blocks, Line,
+    },
+    source_map::ExtractedRuby,
+};
+
+// The Ruby code of a Slim template: `-` and `=` lines, the code after `tag=`, attribute
+// values and `#{}` interpolation in text. Comments (`/`) and embedded engines
+// (`javascript:`) are skipped along with the lines nested in them.
+pub(crate) fn extract_ruby(template: &str) -> ExtractedRuby {
+    let mut extracted = ExtractedRuby::default();
+    let mut blocks = Blocks::default();
+    let mut skipped_indent: Option<usize> = None;
+    // Lines nested in `|` are text
+    let mut text_indent: Option<usize> = None;
+    let mut consumed = 0;
+
+    for line in lines(template) {
+        if line.start < consumed || line.content.is_empty() {
+            continue;
+        }
+        if let Some(indent) = skipped_indent {
+            if line.indent > indent {
+                continue;
+            }
+            skipped_indent = None;
+        }
+        if let Some(indent) = text_indent {
+            if line.indent > indent {
+                push_interpolations(
+                    &mut extracted,
+                    template,
+                    line.start,
+                    line_end(template, line.start),
+                );
+                continue;
+            }
+            text_indent = None;
+        }
+
+        let content = line.content;
+        if content.starts_with('/') || content.starts_with("doctype") || is_embedded_engine(content)
+        {
+            blocks.close(&mut extracted, line.indent, None);
+            skipped_indent = Some(line.indent);
+        } else if let Some(code_start) = code_start(content) {
+            consumed = push_code(
+                &mut extracted,
+                &mut blocks,
+                template,
+                &line,
+                line.start + code_start,
+            );
+        } else if content.starts_with(['|', '\'', '<']) {
+            blocks.close(&mut extracted, line.indent, None);
+            if !content.starts_with('<') {
+                text_indent = Some(line.indent);
+            }
+            push_interpolations(
+                &mut extracted,
+                template,
+                line.start,
+                line_end(template, line.start),
+            );
+        } else {
+            blocks.close(&mut extracted, line.indent, None);
+            consumed = push_tag(&mut extracted, &mut blocks, template, &line, line.start);
+        }
+    }
+
+    blocks.close_all(&mut extracted);
+    extracted
+}
+
+// Where the code starts, for `- code`, `= code`, `== code` and their `'`, `<` and `>`
+// whitespace variants
+fn code_start(content: &str) -> Option<usize> {
+    let prefix_len = if content.starts_with("==") {
+        2
+    } else if content.starts_with(['=', '-']) {
+        1
+    } else {
+        return None;
+    };
+    let modifiers = content[prefix_len..]
+        .bytes()
+        .take_while(|byte| matches!(byte, b'\'' | b'<' | b'>'))
+        .count();
+    Some(prefix_len + modifiers)
+}
+
+// e.g. `javascript:` or `ruby:` on a line of its own
+fn is_embedded_engine(content: &str) -> bool {
+    content.strip_suffix(':').is_some_and(|name| {
+        !name.is_empty()
+            && name
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+    })
+}
+
+// Returns the offset up to which the template is consumed
+fn push_code(
+    extracted: &mut ExtractedRuby,
+    blocks: &mut Blocks,
+    template: &str,
+    line: &Line,
+    start: usize,
+) -> usize {
+    let end = code_end(template, start, &['\\']);
+    let code = &template[start..end];
+    blocks.close(extracted, line.indent, Some(code));
+    extracted.push_snippet(start, code);
+    blocks.open(line.indent, code);
+    end
+}
+
+fn push_tag(
+    extracted: &mut ExtractedRuby,
+    blocks: &mut Blocks,
+    template: &str,
+    line: &Line,
+    start: usize,
+) -> usize {
+    let bytes = template.as_bytes();
+    let mut end_of_line = line_end(template, start);
+
+    // tag.class#id
+    let mut offset = start;
+    while offset < end_of_line
+        && (bytes[offset].is_ascii_alphanumeric()
+            || matches!(bytes[offset], b'.' | b'#' | b'-' | b'_'))
+    {
+        offset += 1;
+    }
+
+    // Attributes, optionally wrapped in (), [] or {} that may span lines
+    let mut wrapper_end = None;
+    if offset < end_of_line && matches!(bytes[offset], b'(' | b'[' | b'{') {
+        if let Some(close) = closing_bracket(template, offset) {
+            wrapper_end = Some(close);
+            offset += 1;
+        }
+    }
+    let attributes_end = wrapper_end.unwrap_or(end_of_line);
+    loop {
+        while offset < attributes_end && bytes[offset].is_ascii_whitespace() {
+            offset += 1;
+        }
+        let name_len = template[offset..attributes_end]
+            .bytes()
+            .take_while(|byte| {
+                byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b':' | b'@')
+            })
+            .count();
+        let after_name = offset + name_len;
+        if name_len == 0 || !template[after_name..attributes_end].starts_with('=') {
+            break;
+        }
+        let value_start = after_name
+            + if template[after_name..].starts_with("==") {
+                2
+            } else {
+                1
+            };
+        offset = push_attribute_value(extracted, template, value_start, attributes_end);
+    }
+    if let Some(close) = wrapper_end {
+        offset = close + 1;
+        end_of_line = line_end(template, offset);
+    }
+
+    let rest = &template[offset..end_of_line];
+    if let Some(nested) = rest.strip_prefix(':') {
+        // `li: a href=url Link` nests a tag on the same line
+        let nested_start = end_of_line - nested.trim_start().len();
+        return push_tag(extracted, blocks, template, line, nested_start);
+    }
+    if rest.starts_with('=') {
+        if let Some(code_start) = code_start(rest) {
+            return push_code(extracted, blocks, template, line, offset + code_start);
+        }
+    }
+    push_interpolations(extracted, template, offset, end_of_line);
+    end_of_line
+}
+
+// Pushes a quoted value's interpolations or an unquoted value's code, and returns its end
+fn push_attribute_value(
+    extracted: &mut ExtractedRuby,
+    template: &str,
+    start: usize,
+    attributes_end: usize,
+) -> usize {
+    let bytes = template.as_bytes();
+    let Some(&first) = bytes.get(start) else {
+        return start;
+    };
+    if first == b'"' || first == b'\'' {
+        let close = template[start + 1..attributes_end]
+            .find(first as char)
+            .map_or(attributes_end, |index| start + 1 + index);
+        push_interpolations(extracted, template, start + 1, close);
+        return (close + 1).min(attributes_end);
+    }
+
+    // Ruby code up to the next whitespace outside of brackets
+    let mut end = start;
+    while end < attributes_end && !bytes[end].is_ascii_whitespace() {
+        if matches!(bytes[end], b'(' | b'[' | b'{') {
+            match closing_bracket(template, end) {
+                Some(close) => end = close,
+                None => break,
+            }
+        }
+        end += 1;
+    }
+    let end = end.min(attributes_end);
+    extracted.push_snippet(start, &template[start..end]);
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_lines_and_blocks() {
+        let template = "\
+ul
+  - users.each do |user|
+    li = user.name
+  - if Feature.enabled?(:admin)
+    == render Admin::Panel.new
+  - else
+    p Nothing to see
+";
+        assert_eq!(
+            extract_ruby(template).ruby,
+            " users.each do |user|\n user.name\nend\n if Feature.enabled?(:admin)\n render Admin::Panel.new\n else\nend"
+        );
+    }
+
+    #[test]
+    fn attributes_and_text() {
+        let template = "\
+section.report#summary data-chart=Charts::Line.name class=\"report #{Theme.current}\"
+  a(href=report_path(Report.first) title='#{Report.title}') Open
+  p.total Total: #{Money.format(report.total)}
+  li: a href=Help.url Help
+  |
+    Multi-line text by #{Author.name}
+";
+        assert_eq!(
+            extract_ruby(template).ruby,
+            "Charts::Line.name\nTheme.current\nreport_path(Report.first)\nReport.title\nMoney.format(report.total)\nHelp.url\nAuthor.name"
+        );
+    }
+
+    #[test]
+    fn comments_and_embedded_engines() {
+        let template = "\
+doctype html
+/ Legacy::Widget.render
+javascript:
+  var user = #{User.first.to_json};
+p= Current.user
+";
+        assert_eq!(extract_ruby(template).ruby, " Current.user");
+    }
+
+    #[test]
+    fn maps_back_to_the_template() {
+        let template = "div\n  p = Admin::User.count\n";
+        let extracted = extract_ruby(template);
+        let ruby_offset = extracted.ruby.find("Admin").unwrap();
+        assert_eq!(
+            extracted.source_map.template_offset(ruby_offset),
+            template.find("Admin").unwrap()
+        );
+    }
+}
@@ -0,0 +1,81 @@
+// Ruby extracted from a template, e.g. the code in the `<% %>` tags of an ERB view, along
+// with where each snippet came from so that locations can be reported in the template.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct ExtractedRuby {
+    pub ruby: String,
+    pub source_map: SourceMap,
+}
+
+impl ExtractedRuby {
+    // Appends a snippet found at `template_offset`, on a line of its own
+    pub fn push_snippet(&mut self, template_offset: usize, snippet: &str) {
+        if !self.ruby.is_empty() {
+            self.ruby.push('\n');
+        }
+        self.source_map.segments.push(Segment {
+            ruby_offset: self.ruby.len(),
+            template_offset,
+            len: snippet.len(),
+        });
+        self.ruby.push_str(snippet);
+    }
+
+    // Appends code that is implied rather than written, like the `end` of a HAML block.
+    // It maps to the end of the snippet before it.
+    pub fn push_synthetic(&mut self, code: &str) {
+        if !self.ruby.is_empty() {
+            self.ruby.push('\n');
+        }
+        self.ruby.push_str(code);
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct SourceMap {
+    // sorted by ruby_offset
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Segment {
+    ruby_offset: usize,
+    template_offset: usize,
+    len: usize,
+}
+
+impl SourceMap {
+    // The template byte offset of a byte offset in the extracted Ruby. Offsets between
+    // snippets map to the end of the preceding snippet.
+    pub fn template_offset(&self, ruby_offset: usize) -> usize {
+        let index = self
+            .segments
+            .partition_point(|segment| segment.ruby_offset <= ruby_offset);
+        match index.checked_sub(1).map(|index| &self.segments[index]) {
+            Some(segment) => {
+                segment.template_offset + (ruby_offset - segment.ruby_offset).min(segment.len)
+            }
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn template_offset() {
+        let mut extracted = ExtractedRuby::default();
+        extracted.push_snippet(4, "foo");
+        extracted.push_snippet(20, "Bar.baz");
+        assert_eq!(extracted.ruby, "foo\nBar.baz");
+
+        let source_map = &extracted.source_map;
+        assert_eq!(source_map.template_offset(0), 4);
+        assert_eq!(source_map.template_offset(2), 6);
+        // the newline between the snippets
+        assert_eq!(source_map.template_offset(3), 7);
+        assert_eq!(source_map.template_offset(4), 20);
+        assert_eq!(source_map.template_offset(11), 27);
+    }
+}
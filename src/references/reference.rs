@@ -9,16 +9,109 @@ use serde::{Deserialize, Serialize};
 use crate::references::{
     configuration::Configuration,
     constant_resolver::{ConstantDefinition, ConstantResolver},
-    parser::{SourceLocation, UnresolvedReference},
+    error::ReferencesError,
+    parser::{Range, SourceLocation, UnresolvedReference},
 };
 
+/// Whether the lines and columns of a `SourceRange` count from 0, as LSP positions do,
+/// or from 1, as editors display them. Byte offsets always count from 0.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum PositionBase {
+    ZeroBased,
+    #[default]
+    OneBased,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SourcePosition {
+    pub line: usize,
+    // counted in bytes
+    pub column: usize,
+    // counted in UTF-16 code units, for editors speaking LSP
+    pub utf16_column: usize,
+    pub byte_offset: usize,
+}
+
+/// The span of the reference in its file. `end` is the position just past the last character.
+#[derive(Debug, Default, PartialEq, Eq, Clone, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SourceRange {
+    pub start: SourcePosition,
+    pub end: SourcePosition,
+}
+
+impl SourceRange {
+    pub(crate) fn from_range(range: &Range, position_base: PositionBase) -> SourceRange {
+        let base = match position_base {
+            PositionBase::ZeroBased => 0,
+            PositionBase::OneBased => 1,
+        };
+        // Range rows and end_col are 1-based, its other columns 0-based
+        SourceRange {
+            start: SourcePosition {
+                line: range.start_row.saturating_sub(1) + base,
+                column: range.start_col + base,
+                utf16_column: range.start_utf16_col + base,
+                byte_offset: range.start_byte,
+            },
+            end: SourcePosition {
+                line: range.end_row.saturating_sub(1) + base,
+                column: range.end_col.saturating_sub(1) + base,
+                utf16_column: range.end_utf16_col + base,
+                byte_offset: range.end_byte,
+            },
+        }
+    }
+}
+
+/// Why a reference exists
+#[derive(
+    Debug, Default, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum ReferenceKind {
+    // Any other use of a constant, e.g. `Foo.new` or `Foo::BAR`
+    #[default]
+    Constant,
+    // `class Foo < Bar` references `Bar`
+    Superclass,
+    // `include Foo`, `extend Foo` or `prepend Foo`
+    Mixin,
+    // `has_many :companies` or `belongs_to :owner, class_name: "User"`, inferred by convention
+    // rather than written as a constant, so heuristic
+    Association,
+    // `class Foo` or `module Foo` itself, which packwerk also considers a reference
+    Definition,
+    // A string or symbol naming a constant, e.g. `"Foo".constantize` or `const_get(:Foo)`.
+    // Only reported when `Configuration::string_constant_references` is set.
+    Dynamic,
+    // A constant in a Sorbet signature, `T.let`/`T.cast` type or T::Struct `prop` type, which
+    // only couples the files at type-check time. Excluded when
+    // `Configuration::exclude_type_only_references` is set.
+    TypeOnly,
+    // Any other use of a constant in an ERB template
+    Erb,
+    // Any other use of a constant in a HAML template
+    Haml,
+    // Any other use of a constant in a Slim template
+    Slim,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Reference {
     pub constant_name: String,
     pub relative_defining_file: Option<String>,
     pub relative_referencing_file: String,
+    // 1-based line and 0-based column of the start, as packwerk reports them
     pub source_location: SourceLocation,
+    // Start and end, based as configured by Configuration::position_base
+    #[serde(default)]
+    pub source_range: SourceRange,
     pub extra_fields: HashMap<String, String>,
+    // The constant is a module Zeitwerk defines for a directory without a matching file,
+    // so relative_defining_file is that directory
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub implicit_namespace: bool,
+    #[serde(default)]
+    pub kind: ReferenceKind,
 }
 
 impl Ord for Reference {
@@ -40,10 +133,23 @@ impl Ord for Reference {
                     .column
                     .cmp(&other.source_location.column)
             })
+            .then_with(|| self.source_range.cmp(&other.source_range))
             .then_with(|| self.extra_fields.len().cmp(&other.extra_fields.len()))
+            .then_with(|| {
+                sorted_entries(&self.extra_fields).cmp(&sorted_entries(&other.extra_fields))
+            })
+            .then_with(|| self.kind.cmp(&other.kind))
+            .then_with(|| self.implicit_namespace.cmp(&other.implicit_namespace))
     }
 }
 
+// Compares equal exactly when the maps are equal, as the order of a HashMap is arbitrary
+fn sorted_entries(map: &HashMap<String, String>) -> Vec<(&String, &String)> {
+    let mut entries = map.iter().collect::<Vec<(&String, &String)>>();
+    entries.sort();
+    entries
+}
+
 impl PartialOrd for Reference {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -56,13 +162,13 @@ impl Reference {
         constant_resolver: &(dyn ConstantResolver + Send + Sync),
         unresolved_reference: &UnresolvedReference,
         referencing_file_path: &Path,
-    ) -> anyhow::Result<Vec<Reference>> {
-        ReferencesBuilder::default()
+    ) -> Result<Vec<Reference>, ReferencesError> {
+        Ok(ReferencesBuilder::default()
             .configuration(configuration)
             .constant_resolver(constant_resolver)
             .referencing_file_path(referencing_file_path)?
             .unresolved_reference(unresolved_reference)?
-            .build()
+            .build()?)
     }
 }
 #[derive(Default)]
@@ -71,9 +177,11 @@ struct ReferencesBuilder<'a> {
     referencing_file_path: Option<PathBuf>,
     relative_referencing_file: String,
     source_location: Option<SourceLocation>,
+    source_range: SourceRange,
     constant_resolver: Option<&'a (dyn ConstantResolver + Send + Sync)>,
     constant_definition: Option<Vec<ConstantDefinition>>,
     unresolved_reference_name: Option<String>,
+    kind: ReferenceKind,
 }
 
 impl<'a> ReferencesBuilder<'a> {
@@ -92,26 +200,25 @@ impl<'a> ReferencesBuilder<'a> {
             .unresolved_reference_name
             .context("expected unresolved_reference_name")?
             .clone();
+        let referencing_file_path = self
+            .referencing_file_path
+            .context("expecting referencing_file_path")?;
         let extra_fields = self
             .configuration
             .context("expecting configuration")?
             .extra_reference_fields_fn
             .as_ref()
-            .map(|fn_| {
-                fn_.extra_reference_fields_fn(
-                    &self
-                        .referencing_file_path
-                        .expect("expecting relative_referencing_file_path"),
-                    None,
-                )
-            })
+            .map(|fn_| fn_.extra_reference_fields_fn(&referencing_file_path, None))
             .unwrap_or_default();
         Ok(vec![Reference {
             constant_name,
             relative_referencing_file: self.relative_referencing_file,
             source_location: self.source_location.context("expecting source_location")?,
+            source_range: self.source_range,
             relative_defining_file,
             extra_fields,
+            implicit_namespace: false,
+            kind: self.kind,
         }])
     }
 
@@ -124,36 +231,54 @@ impl<'a> ReferencesBuilder<'a> {
             .context("expecting configuration")?
             .absolute_root
             .clone();
-        Ok(constant_definitions
-                .iter()
-                .map(move |constant| {
-                    let absolute_path_of_definition = &constant.absolute_path_of_definition;
-                    let relative_defining_file = absolute_path_of_definition
-                        .strip_prefix(&absolute_root)
-                        .context(format!("expecting strip_prefix. absolute_path_of_definition: {:?}, absolute_root: {:?}", &absolute_path_of_definition, &absolute_root))?
-                        .to_path_buf()
-                        .to_str()
-                        .context("expecting relative_defining_file")?
-                        .to_string();
-
-                    let relative_defining_file = Some(relative_defining_file);
-                    let constant_name = constant.fully_qualified_name.clone();
-                    let extra_fields = self.configuration
-                        .context("expecting configuration")?
-                        .extra_reference_fields_fn
-                        .as_ref()
-                        .map(|fn_| fn_.extra_reference_fields_fn(&self.referencing_file_path.clone().expect("expecting referencing_file_path"), Some(absolute_path_of_definition)))
-                        .unwrap_or_default();
-
-                    Ok(Reference {
-                        constant_name,
-                        relative_referencing_file: self.relative_referencing_file.clone(),
-                        source_location: self.source_location.clone().context("expecting source_location")?.clone(),
-                        relative_defining_file,
-                        extra_fields,
+        let referencing_file_path = self
+            .referencing_file_path
+            .clone()
+            .context("expecting referencing_file_path")?;
+        constant_definitions
+            .iter()
+            .map(move |constant| {
+                let absolute_path_of_definition = &constant.absolute_path_of_definition;
+                let relative_defining_file = absolute_path_of_definition
+                    .strip_prefix(&absolute_root)
+                    .map_err(|_| ReferencesError::PathOutsideRoot {
+                        path: absolute_path_of_definition.to_owned(),
+                        absolute_root: absolute_root.to_owned(),
+                    })?
+                    .to_string_lossy()
+                    .to_string();
+
+                let relative_defining_file = Some(relative_defining_file);
+                let constant_name = constant.fully_qualified_name.clone();
+                let extra_fields = self
+                    .configuration
+                    .context("expecting configuration")?
+                    .extra_reference_fields_fn
+                    .as_ref()
+                    .map(|fn_| {
+                        fn_.extra_reference_fields_fn(
+                            &referencing_file_path,
+                            Some(absolute_path_of_definition),
+                        )
                     })
+                    .unwrap_or_default();
+
+                Ok(Reference {
+                    constant_name,
+                    relative_referencing_file: self.relative_referencing_file.clone(),
+                    source_location: self
+                        .source_location
+                        .clone()
+                        .context("expecting source_location")?
+                        .clone(),
+                    source_range: self.source_range.clone(),
+                    relative_defining_file,
+                    extra_fields,
+                    implicit_namespace: constant.implicit_namespace,
+                    kind: self.kind,
                 })
-                .collect::<anyhow::Result<Vec<Reference>>>()?)
+            })
+            .collect::<anyhow::Result<Vec<Reference>>>()
     }
 
     fn configuration(mut self, configuration: &'a Configuration) -> Self {
@@ -170,15 +295,12 @@ impl<'a> ReferencesBuilder<'a> {
 
         let relative_referencing_file_path = referencing_file_path
             .strip_prefix(&absolute_root)
-            .context(format!(
-                "expecting strip_prefix. referencing_file_path: {:?}, absolute_root: {:?}",
-                &referencing_file_path, absolute_root
-            ))?
-            .to_path_buf();
-        self.relative_referencing_file = relative_referencing_file_path
-            .to_str()
-            .context("expecting relative_referencing_file_path to allow to_str")?
-            .to_string();
+            .map_err(|_| ReferencesError::PathOutsideRoot {
+                path: referencing_file_path.to_path_buf(),
+                absolute_root: absolute_root.to_owned(),
+            })?;
+        self.relative_referencing_file =
+            relative_referencing_file_path.to_string_lossy().to_string();
         self.referencing_file_path = Some(referencing_file_path.to_path_buf());
         Ok(self)
     }
@@ -192,6 +314,11 @@ impl<'a> ReferencesBuilder<'a> {
             line: loc.start_row,
             column: loc.start_col,
         });
+        let position_base = self
+            .configuration
+            .context("expecting configuration")?
+            .position_base;
+        self.source_range = SourceRange::from_range(loc, position_base);
 
         let str_namespace_path: Vec<&str> = unresolved_reference
             .namespace_path
@@ -204,6 +331,7 @@ impl<'a> ReferencesBuilder<'a> {
             .context("expecting constant_resolver")?
             .resolve(&unresolved_reference.name, &str_namespace_path);
         self.unresolved_reference_name = Some(unresolved_reference.name.clone());
+        self.kind = unresolved_reference.kind;
 
         Ok(self)
     }
@@ -216,3 +344,52 @@ impl<'a> ReferencesBuilder<'a> {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordering_agrees_with_equality() {
+        let reference = Reference {
+            constant_name: String::from("::Foo"),
+            relative_defining_file: Some(String::from("app/models/foo.rb")),
+            relative_referencing_file: String::from("app/models/bar.rb"),
+            source_location: SourceLocation { line: 1, column: 0 },
+            source_range: SourceRange::default(),
+            extra_fields: HashMap::from([(String::from("pack"), String::from("a"))]),
+            implicit_namespace: false,
+            kind: ReferenceKind::Constant,
+        };
+        let variants = [
+            Reference {
+                source_range: SourceRange {
+                    end: SourcePosition {
+                        byte_offset: 3,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..reference.clone()
+            },
+            Reference {
+                extra_fields: HashMap::from([(String::from("pack"), String::from("b"))]),
+                ..reference.clone()
+            },
+            Reference {
+                implicit_namespace: true,
+                ..reference.clone()
+            },
+            Reference {
+                kind: ReferenceKind::Mixin,
+                ..reference.clone()
+            },
+        ];
+
+        assert_eq!(reference.cmp(&reference.clone()), std::cmp::Ordering::Equal);
+        for variant in &variants {
+            assert_ne!(&reference, variant);
+            assert_ne!(reference.cmp(variant), std::cmp::Ordering::Equal);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::references::reference::Reference;
+
+/// References indexed by constant, referencing file, defining file and `extra_fields`.
+/// Serializes with its indexes, so that it can be built once and queried by many tools.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ReferenceIndex {
+    references: Vec<Reference>,
+    // Each index maps a key to positions in `references`, in the order of `references`
+    by_constant_name: HashMap<String, Vec<usize>>,
+    by_referencing_file: HashMap<String, Vec<usize>>,
+    by_defining_file: HashMap<String, Vec<usize>>,
+    // extra field key => extra field value => positions
+    by_extra_field: HashMap<String, HashMap<String, Vec<usize>>>,
+}
+
+impl ReferenceIndex {
+    pub fn new(mut references: Vec<Reference>) -> ReferenceIndex {
+        references.sort();
+
+        let mut index = ReferenceIndex::default();
+        for (position, reference) in references.iter().enumerate() {
+            index
+                .by_constant_name
+                .entry(reference.constant_name.to_owned())
+                .or_default()
+                .push(position);
+            index
+                .by_referencing_file
+                .entry(reference.relative_referencing_file.to_owned())
+                .or_default()
+                .push(position);
+            if let Some(relative_defining_file) = &reference.relative_defining_file {
+                index
+                    .by_defining_file
+                    .entry(relative_defining_file.to_owned())
+                    .or_default()
+                    .push(position);
+            }
+            for (key, value) in &reference.extra_fields {
+                index
+                    .by_extra_field
+                    .entry(key.to_owned())
+                    .or_default()
+                    .entry(value.to_owned())
+                    .or_default()
+                    .push(position);
+            }
+        }
+        index.references = references;
+        index
+    }
+
+    /// Every indexed reference, sorted
+    pub fn references(&self) -> &[Reference] {
+        &self.references
+    }
+
+    /// References whose `constant_name` is exactly `constant_name`, e.g. `::Foo::Bar`
+    pub fn references_to_constant(&self, constant_name: &str) -> Vec<&Reference> {
+        self.lookup(self.by_constant_name.get(constant_name))
+    }
+
+    /// References made in `relative_referencing_file`
+    pub fn references_from_file(&self, relative_referencing_file: &str) -> Vec<&Reference> {
+        self.lookup(self.by_referencing_file.get(relative_referencing_file))
+    }
+
+    /// References to constants defined in `relative_defining_file`
+    pub fn references_into_file(&self, relative_defining_file: &str) -> Vec<&Reference> {
+        self.lookup(self.by_defining_file.get(relative_defining_file))
+    }
+
+    /// References whose extra field `key` is `value`, e.g. `referencing_pack_name` and `packs/foo`
+    pub fn references_with_extra_field(&self, key: &str, value: &str) -> Vec<&Reference> {
+        self.lookup(
+            self.by_extra_field
+                .get(key)
+                .and_then(|by_value| by_value.get(value)),
+        )
+    }
+
+    /// References grouped by the value of their extra field `key`, leaving out references
+    /// without it
+    pub fn references_by_extra_field(&self, key: &str) -> BTreeMap<&str, Vec<&Reference>> {
+        self.by_extra_field
+            .get(key)
+            .map(|by_value| {
+                by_value
+                    .iter()
+                    .map(|(value, positions)| (value.as_str(), self.lookup(Some(positions))))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // Positions come from deserialized input, so any that are out of bounds are skipped
+    fn lookup(&self, positions: Option<&Vec<usize>>) -> Vec<&Reference> {
+        positions
+            .map(|positions| {
+                positions
+                    .iter()
+                    .filter_map(|position| self.references.get(*position))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl From<Vec<Reference>> for ReferenceIndex {
+    fn from(references: Vec<Reference>) -> Self {
+        ReferenceIndex::new(references)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::references::{
+        all_references,
+        common_test::common_test::{configuration_for_fixture, SIMPLE_APP},
+    };
+    use pretty_assertions::assert_eq;
+
+    fn referencing_files<'a>(references: &[&'a Reference]) -> Vec<&'a str> {
+        references
+            .iter()
+            .map(|reference| reference.relative_referencing_file.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn lookups() -> anyhow::Result<()> {
+        let references = all_references(&configuration_for_fixture(SIMPLE_APP, false))?;
+        let index = ReferenceIndex::new(references);
+
+        assert_eq!(index.references().len(), 11);
+        assert_eq!(
+            referencing_files(&index.references_to_constant("::Bar")),
+            vec![
+                "packs/bar/app/services/bar.rb",
+                "packs/foo/app/services/foo.rb"
+            ]
+        );
+        assert!(index.references_to_constant("Bar").is_empty());
+
+        let constants = index
+            .references_from_file("packs/foo/app/services/foo.rb")
+            .iter()
+            .map(|reference| reference.constant_name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(constants, vec!["::Bar", "::Baz", "::Foo"]);
+
+        assert_eq!(
+            referencing_files(&index.references_into_file("packs/foo/app/services/foo.rb")),
+            vec![
+                "packs/foo/app/services/foo.rb",
+                "packs/foo/app/services/foo/bar.rb"
+            ]
+        );
+
+        assert_eq!(
+            index
+                .references_with_extra_field("defining_pack_name", "packs/baz")
+                .len(),
+            1
+        );
+        let by_pack = index
+            .references_by_extra_field("referencing_pack_name")
+            .into_iter()
+            .map(|(pack, references)| (pack, references.len()))
+            .collect::<Vec<(&str, usize)>>();
+        assert_eq!(by_pack, vec![(".", 4), ("packs/bar", 2), ("packs/foo", 5)]);
+        assert!(index.references_by_extra_field("owner").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn serialization_round_trip() -> anyhow::Result<()> {
+        let references = all_references(&configuration_for_fixture(SIMPLE_APP, false))?;
+        let index = ReferenceIndex::from(references);
+
+        let json = serde_json::to_string(&index)?;
+        let deserialized: ReferenceIndex = serde_json::from_str(&json)?;
+
+        assert_eq!(deserialized, index);
+        assert_eq!(
+            deserialized.references_to_constant("::Baz"),
+            index.references_to_constant("::Baz")
+        );
+        Ok(())
+    }
+}
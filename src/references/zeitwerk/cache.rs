@@ -7,6 +7,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::references::{
     cache::create_cache_dir_idempotently, constant_resolver::ConstantDefinition,
+    error::ReferencesError,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -14,16 +15,24 @@ pub(crate) struct ConstantResolverCache {
     pub(crate) file_definition_map: HashMap<PathBuf, String>,
 }
 
-pub(crate) fn get_constant_resolver_cache(cache_dir: &Path) -> ConstantResolverCache {
+pub(crate) fn get_constant_resolver_cache(
+    cache_dir: &Path,
+) -> Result<ConstantResolverCache, ReferencesError> {
     let path = cache_dir.join("constant_resolver.json");
     if path.exists() {
-        let file = std::fs::File::open(path).unwrap();
+        let file = std::fs::File::open(&path).map_err(|source| ReferencesError::CacheIo {
+            path: path.to_owned(),
+            source,
+        })?;
         let reader = std::io::BufReader::new(file);
-        serde_json::from_reader(reader).unwrap()
+        serde_json::from_reader(reader).map_err(|e| ReferencesError::CorruptCache {
+            path,
+            message: e.to_string(),
+        })
     } else {
-        ConstantResolverCache {
+        Ok(ConstantResolverCache {
             file_definition_map: HashMap::new(),
-        }
+        })
     }
 }
 
@@ -31,9 +40,9 @@ pub(crate) fn write_cache_constant_definitions(
     constants: &Vec<ConstantDefinition>,
     cache_dir: &Path,
     cache_enabled: bool,
-) {
+) -> Result<(), ReferencesError> {
     if !cache_enabled {
-        return;
+        return Ok(());
     }
 
     let mut file_definition_map: HashMap<PathBuf, String> = HashMap::new();
@@ -47,8 +56,38 @@ pub(crate) fn write_cache_constant_definitions(
     let cache_data_json = serde_json::to_string(&ConstantResolverCache {
         file_definition_map,
     })
-    .expect("Failed to serialize");
+    .map_err(|e| ReferencesError::Internal {
+        context: format!("Failed to serialize constant resolver cache: {}", e),
+    })?;
 
     let _ = create_cache_dir_idempotently(cache_dir);
-    std::fs::write(cache_dir.join("constant_resolver.json"), cache_data_json).unwrap();
+    let path = cache_dir.join("constant_resolver.json");
+    std::fs::write(&path, cache_data_json)
+        .map_err(|source| ReferencesError::CacheIo { path, source })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrupt_cache() -> anyhow::Result<()> {
+        let cache_dir = std::env::temp_dir().join("ruby-references-corrupt-constant-resolver");
+        create_cache_dir_idempotently(&cache_dir)?;
+        std::fs::write(
+            cache_dir.join("constant_resolver.json"),
+            r#"{"file_definition_map":"#,
+        )?;
+
+        let result = get_constant_resolver_cache(&cache_dir);
+        std::fs::remove_dir_all(&cache_dir)?;
+
+        match result {
+            Err(ReferencesError::CorruptCache { path, .. }) => {
+                assert_eq!(path, cache_dir.join("constant_resolver.json"))
+            }
+            _ => panic!("expected a corrupt cache error"),
+        }
+        Ok(())
+    }
 }
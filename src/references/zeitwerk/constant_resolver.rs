@@ -1,6 +1,9 @@
-use tracing::debug;
+use tracing::{debug, warn};
 
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
 
 use crate::references::{
     constant_resolver::{ConstantDefinition, ConstantResolver},
@@ -11,6 +14,9 @@ use crate::references::{
 pub struct ZeitwerkConstantResolver {
     pub fully_qualified_constant_name_to_constant_definition_map:
         HashMap<String, Vec<ConstantDefinition>>,
+    // Files that reopen a constant owned elsewhere. Their definitions are kept in the map,
+    // but references never resolve to them.
+    pub ignored_monkeypatches: HashSet<PathBuf>,
 }
 
 impl ConstantResolver for ZeitwerkConstantResolver {
@@ -44,34 +50,45 @@ impl ConstantResolver for ZeitwerkConstantResolver {
 }
 
 impl ZeitwerkConstantResolver {
-    pub fn create(constants: Vec<ConstantDefinition>) -> Box<dyn ConstantResolver + Send + Sync> {
+    pub fn create(
+        constants: Vec<ConstantDefinition>,
+        ignored_monkeypatches: &HashSet<PathBuf>,
+    ) -> Box<dyn ConstantResolver + Send + Sync> {
         debug!("Building constant resolver from constants vector");
 
         let mut fully_qualified_constant_to_constant_map: HashMap<String, Vec<ConstantDefinition>> =
             HashMap::new();
 
-        // TODO: Do this in parallel?
+        // A constant may be defined by several files, e.g. when two packs each define `Widget`.
+        // Every definition is kept so that a reference can point at each of them.
         for constant in constants {
-            let fully_qualified_constant_name = constant.fully_qualified_name.clone();
-
-            let existing_constant =
-                fully_qualified_constant_to_constant_map.get(&fully_qualified_constant_name);
-
-            if let Some(existing_constant) = existing_constant {
-                // TODO: This still needs to be handled more elegantly. For now, we just panic.
-                // Probably, we should have the HashMap have a Vec<Constant> instead of a single Constant, and then we can add to the Vec.
-                // Then, when we create references, we can create one reference to each unique pack that defines the constant.
-
-                // Later, we can allow the checkers to skip over constants where it's pointing at a pack that defines it as an ignored_monkeypatch: path/to/definition.rb
-                // We should be sure to validate that ignored_monkeypatch paths match the absolute_path_to_definition of the constant.
-                panic!(
-                    "Found two constants with the same name: {:?} and {:?}",
-                    existing_constant, constant
-                );
-            } else {
-                fully_qualified_constant_to_constant_map
-                    .insert(fully_qualified_constant_name, vec![constant]);
-            }
+            fully_qualified_constant_to_constant_map
+                .entry(constant.fully_qualified_name.clone())
+                .or_default()
+                .push(constant);
+        }
+        for definitions in fully_qualified_constant_to_constant_map.values_mut() {
+            definitions.sort_by(|a, b| {
+                a.absolute_path_of_definition
+                    .cmp(&b.absolute_path_of_definition)
+            });
+        }
+
+        let mut ignored_monkeypatches_without_definitions = ignored_monkeypatches
+            .iter()
+            .filter(|path| {
+                !fully_qualified_constant_to_constant_map
+                    .values()
+                    .flatten()
+                    .any(|constant| &constant.absolute_path_of_definition == *path)
+            })
+            .collect::<Vec<&PathBuf>>();
+        ignored_monkeypatches_without_definitions.sort();
+        for path in ignored_monkeypatches_without_definitions {
+            warn!(
+                "Ignored monkeypatch {:?} does not define an autoloaded constant",
+                path
+            );
         }
 
         debug!("Finished building constant resolver");
@@ -79,6 +96,7 @@ impl ZeitwerkConstantResolver {
         Box::new(ZeitwerkConstantResolver {
             fully_qualified_constant_name_to_constant_definition_map:
                 fully_qualified_constant_to_constant_map,
+            ignored_monkeypatches: ignored_monkeypatches.clone(),
         })
     }
 
@@ -94,7 +112,9 @@ impl ZeitwerkConstantResolver {
             original_name,
         );
         match constant {
-            Some(definition) => Some(vec![definition]),
+            // Every definition of the constant is an ignored monkeypatch
+            Some(definitions) if definitions.is_empty() => None,
+            Some(definitions) => Some(definitions),
             None => {
                 // If we couldn't find a match, it's possible the constant is defined within its parent namespace and not within its own file.
                 // For example, `Boo` above could be defined in `foo/bar.rb` as:
@@ -143,11 +163,12 @@ impl ZeitwerkConstantResolver {
         const_name: &'a str,
         current_namespace_path: &'a [&str],
         original_name: &'a str,
-    ) -> Option<ConstantDefinition> {
+    ) -> Option<Vec<ConstantDefinition>> {
         let fully_qualified_name_guess =
             combine_namespace_with_constant_name(current_namespace_path, const_name);
 
-        if let Some(constant) = self.constant_for_fully_qualified_name(&fully_qualified_name_guess)
+        if let Some(definitions) =
+            self.definitions_for_fully_qualified_name(&fully_qualified_name_guess)
         {
             // Since the ContantResolver might say that some constant Foo::Bar::Baz is defined in Foo::Bar,
             // we want to return a ConstantDefinition that has the fully qualified name of the constant we're looking for.
@@ -158,11 +179,23 @@ impl ZeitwerkConstantResolver {
             let fully_qualified_name =
                 combine_namespace_with_constant_name(current_namespace_path, original_name);
 
-            let absolute_path_of_definition = constant.absolute_path_of_definition.to_owned();
-            Some(ConstantDefinition {
-                fully_qualified_name,
-                absolute_path_of_definition,
-            })
+            Some(
+                definitions
+                    .iter()
+                    .filter(|constant| {
+                        !self
+                            .ignored_monkeypatches
+                            .contains(&constant.absolute_path_of_definition)
+                    })
+                    .map(|constant| ConstantDefinition {
+                        fully_qualified_name: fully_qualified_name.clone(),
+                        absolute_path_of_definition: constant
+                            .absolute_path_of_definition
+                            .to_owned(),
+                        implicit_namespace: constant.implicit_namespace,
+                    })
+                    .collect(),
+            )
         } else {
             // In this case, we couldn't find a constant with the given name under the given namespace.
             // However, it's possible the constant is defined within the parent namespace.
@@ -178,17 +211,11 @@ impl ZeitwerkConstantResolver {
         }
     }
 
-    fn constant_for_fully_qualified_name(
+    fn definitions_for_fully_qualified_name(
         &self,
         fully_qualified_name: &String,
-    ) -> Option<&ConstantDefinition> {
-        if let Some(definitions) = self
-            .fully_qualified_constant_name_to_constant_definition_map
+    ) -> Option<&Vec<ConstantDefinition>> {
+        self.fully_qualified_constant_name_to_constant_definition_map
             .get(fully_qualified_name)
-        {
-            return Some(definitions.first().unwrap());
-        }
-
-        None
     }
 }
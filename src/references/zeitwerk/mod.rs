@@ -6,41 +6,150 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use globset::GlobSet;
 use rayon::iter::{ParallelBridge, ParallelIterator};
 use tracing::debug;
 
 use crate::references::{
     configuration::Configuration,
     constant_resolver::{ConstantDefinition, ConstantResolver},
-    parser::inflector_shim,
+    error::ReferencesError,
+    inflections::Inflections,
+    packwerk_config::build_glob_set,
+    parser::{inflector_shim, ProcessedFile},
 };
 
 pub fn get_zeitwerk_constant_resolver(
     configuration: &Configuration,
-) -> Box<dyn ConstantResolver + Send + Sync> {
-    let constants = inferred_constants(configuration);
+    processed_files: &[ProcessedFile],
+) -> anyhow::Result<Box<dyn ConstantResolver + Send + Sync>> {
+    let mut constants = inferred_constants(configuration)?;
+    if configuration.index_parsed_definitions {
+        let parsed_constants = parsed_constants(processed_files, &constants);
+        constants.extend(parsed_constants);
+    }
+
+    Ok(
+        crate::references::zeitwerk::constant_resolver::ZeitwerkConstantResolver::create(
+            constants,
+            &configuration.ignored_monkeypatches,
+        ),
+    )
+}
+
+// The constant each autoloaded file is expected to define, without implicit namespaces
+pub(crate) fn autoloaded_constants(
+    configuration: &Configuration,
+) -> anyhow::Result<Vec<ConstantDefinition>> {
+    Ok(inferred_constants(configuration)?
+        .into_iter()
+        .filter(|constant| !constant.implicit_namespace)
+        .collect())
+}
+
+// The equivalent of `Rails.autoloaders.main.collapse(...)` and `.ignore(...)`,
+// with globs relative to the application root.
+struct AutoloadRules<'a> {
+    absolute_root: &'a Path,
+    collapse: GlobSet,
+    ignore: GlobSet,
+}
+
+impl<'a> AutoloadRules<'a> {
+    fn new(configuration: &'a Configuration) -> anyhow::Result<AutoloadRules<'a>> {
+        Ok(AutoloadRules {
+            absolute_root: &configuration.absolute_root,
+            collapse: build_glob_set(&configuration.zeitwerk_collapse).map_err(|e| {
+                ReferencesError::invalid_key(None, "zeitwerk_collapse", format!("{:#}", e))
+            })?,
+            ignore: build_glob_set(&configuration.zeitwerk_ignore).map_err(|e| {
+                ReferencesError::invalid_key(None, "zeitwerk_ignore", format!("{:#}", e))
+            })?,
+        })
+    }
 
-    crate::references::zeitwerk::constant_resolver::ZeitwerkConstantResolver::create(constants)
+    fn matches(&self, glob_set: &GlobSet, absolute_path: &Path) -> bool {
+        absolute_path
+            .strip_prefix(self.absolute_root)
+            .is_ok_and(|relative_path| glob_set.is_match(relative_path))
+    }
+
+    // A file is ignored when it, or any directory containing it, is ignored
+    fn is_ignored(&self, absolute_path: &Path) -> bool {
+        absolute_path
+            .ancestors()
+            .any(|path| self.matches(&self.ignore, path))
+    }
+
+    // The paths that name the constant and each of its namespaces: relative to the autoload
+    // path, without extension, and without collapsed directories. Each is paired with the
+    // directory or file it is inferred from; the file itself comes last.
+    fn constant_paths(
+        &self,
+        absolute_path: &Path,
+        absolute_autoload_path: &Path,
+    ) -> Vec<(PathBuf, PathBuf)> {
+        let mut constant_paths = vec![];
+        let mut constant_path = PathBuf::new();
+        let mut current_path = absolute_autoload_path.to_path_buf();
+        let relative_path = absolute_path
+            .strip_prefix(absolute_autoload_path)
+            .unwrap_or(absolute_path);
+        let mut components = relative_path.components().peekable();
+        while let Some(component) = components.next() {
+            current_path.push(component);
+            let is_directory = components.peek().is_some();
+            if is_directory && self.matches(&self.collapse, &current_path) {
+                continue;
+            }
+            constant_path.push(component);
+            constant_paths.push((current_path.clone(), constant_path.clone()));
+        }
+        if let Some((_, file_constant_path)) = constant_paths.last_mut() {
+            file_constant_path.set_extension("");
+        }
+        constant_paths
+    }
+
+    fn constant_path(&self, absolute_path: &Path, absolute_autoload_path: &Path) -> PathBuf {
+        self.constant_paths(absolute_path, absolute_autoload_path)
+            .pop()
+            .map(|(_, constant_path)| constant_path)
+            .unwrap_or_default()
+    }
 }
 
-fn inferred_constants(configuration: &Configuration) -> Vec<ConstantDefinition> {
-    let cache_data = cache::get_constant_resolver_cache(&configuration.cache_directory);
+fn inferred_constants(configuration: &Configuration) -> anyhow::Result<Vec<ConstantDefinition>> {
+    let autoload_rules = AutoloadRules::new(configuration)?;
+    let cache_data = cache::get_constant_resolver_cache(&configuration.cache_directory)?;
     // First, we get a map of each autoload path to the files they map to.
     let autoload_paths_to_their_globbed_files = configuration
         .autoload_paths
         .keys()
         .par_bridge()
-        .map(|absolute_autoload_path| {
-            let glob_path = absolute_autoload_path.join("**/*.rb");
-
-            let files = glob::glob(glob_path.to_str().unwrap())
-                .expect("Failed to read glob pattern")
-                .filter_map(Result::ok)
-                .collect::<Vec<PathBuf>>();
-
-            (absolute_autoload_path, files)
-        })
-        .collect::<HashMap<&PathBuf, Vec<PathBuf>>>();
+        .map(
+            |absolute_autoload_path| -> anyhow::Result<(&PathBuf, Vec<PathBuf>)> {
+                // Escaped, since directory names may contain glob metacharacters like `[`
+                let glob_path = format!(
+                    "{}/**/*.rb",
+                    glob::Pattern::escape(&absolute_autoload_path.to_string_lossy())
+                );
+
+                let files = glob::glob(&glob_path)
+                    .map_err(|e| {
+                        ReferencesError::invalid_key(
+                            None,
+                            "autoload_paths",
+                            format!("{}: {}", absolute_autoload_path.display(), e),
+                        )
+                    })?
+                    .filter_map(Result::ok)
+                    .collect::<Vec<PathBuf>>();
+
+                Ok((absolute_autoload_path, files))
+            },
+        )
+        .collect::<anyhow::Result<HashMap<&PathBuf, Vec<PathBuf>>>>()?;
 
     debug!("Finding autoload path for each file");
     // Then, we want to know *which* autoload path is the one that defines a given constant.
@@ -67,30 +176,34 @@ fn inferred_constants(configuration: &Configuration) -> Vec<ConstantDefinition>
         }
     }
 
-    debug!("Inferring constants from file name");
-    let constants: Vec<ConstantDefinition> = file_to_longest_path
+    let autoloaded_files = file_to_longest_path
         .into_iter()
+        .filter(|(absolute_path, _)| !autoload_rules.is_ignored(absolute_path))
+        .collect::<Vec<(&PathBuf, &PathBuf)>>();
+
+    debug!("Inferring constants from file name");
+    let mut constants: Vec<ConstantDefinition> = autoloaded_files
+        .iter()
         .par_bridge()
-        .map(|(absolute_path_of_definition, absolute_autoload_path)| {
+        .filter_map(|&(absolute_path_of_definition, absolute_autoload_path)| {
             if let Some(fully_qualified_name) = cache_data
                 .file_definition_map
                 .get(absolute_path_of_definition)
             {
-                ConstantDefinition {
+                Some(ConstantDefinition {
                     fully_qualified_name: fully_qualified_name.to_owned(),
-                    absolute_path_of_definition: absolute_path_of_definition.to_owned(),
-                }
+                    absolute_path_of_definition: absolute_path_of_definition.to_path_buf(),
+                    implicit_namespace: false,
+                })
             } else {
-                let default_namespace = configuration
-                    .autoload_paths
-                    .get(absolute_autoload_path)
-                    .unwrap();
-                inferred_constant_from_file(
+                let default_namespace = configuration.autoload_paths.get(absolute_autoload_path)?;
+                Some(inferred_constant_from_file(
                     absolute_path_of_definition,
-                    absolute_autoload_path,
-                    &configuration.acronyms,
+                    &autoload_rules
+                        .constant_path(absolute_path_of_definition, absolute_autoload_path),
+                    &configuration.inflections,
                     default_namespace,
-                )
+                ))
             }
         })
         .collect::<Vec<ConstantDefinition>>();
@@ -99,28 +212,112 @@ fn inferred_constants(configuration: &Configuration) -> Vec<ConstantDefinition>
         &constants,
         &configuration.reference_cache_dir(),
         configuration.cache_enabled,
+    )?;
+
+    debug!("Inferring implicit namespaces from directory names");
+    let implicit_namespaces = implicit_namespaces(
+        &autoloaded_files,
+        &constants,
+        &autoload_rules,
+        configuration,
     );
+    constants.extend(implicit_namespaces);
 
-    constants
+    Ok(constants)
+}
+
+// Zeitwerk autovivifies a module for each directory whose namespace is not defined by a file,
+// e.g. `Payments` for app/services/payments/ when there is no app/services/payments.rb.
+// The directory is the location of its definition.
+fn implicit_namespaces(
+    autoloaded_files: &[(&PathBuf, &PathBuf)],
+    constants: &[ConstantDefinition],
+    autoload_rules: &AutoloadRules,
+    configuration: &Configuration,
+) -> Vec<ConstantDefinition> {
+    let explicit_constant_names = constants
+        .iter()
+        .map(|constant| constant.fully_qualified_name.as_str())
+        .collect::<HashSet<&str>>();
+
+    let mut implicit_namespaces: HashMap<PathBuf, ConstantDefinition> = HashMap::new();
+    for (absolute_path, absolute_autoload_path) in autoloaded_files {
+        let Some(default_namespace) = configuration.autoload_paths.get(*absolute_autoload_path)
+        else {
+            continue;
+        };
+        let mut constant_paths =
+            autoload_rules.constant_paths(absolute_path, absolute_autoload_path);
+        constant_paths.pop();
+        for (absolute_directory, constant_path) in constant_paths {
+            if implicit_namespaces.contains_key(&absolute_directory) {
+                continue;
+            }
+            let namespace = inferred_constant_from_file(
+                &absolute_directory,
+                &constant_path,
+                &configuration.inflections,
+                default_namespace,
+            );
+            if !explicit_constant_names.contains(namespace.fully_qualified_name.as_str()) {
+                implicit_namespaces.insert(
+                    absolute_directory,
+                    ConstantDefinition {
+                        implicit_namespace: true,
+                        ..namespace
+                    },
+                );
+            }
+        }
+    }
+    implicit_namespaces.into_values().collect()
+}
+
+// Constants the parser found defined in a file but that Zeitwerk does not infer, e.g. classes
+// in lib/ or `Bar::BAR` assigned in bar.rb. Zeitwerk's inference wins for names it knows.
+fn parsed_constants(
+    processed_files: &[ProcessedFile],
+    inferred_constants: &[ConstantDefinition],
+) -> Vec<ConstantDefinition> {
+    let inferred_constant_names = inferred_constants
+        .iter()
+        .map(|constant| constant.fully_qualified_name.as_str())
+        .collect::<HashSet<&str>>();
+
+    let mut parsed_constants: Vec<ConstantDefinition> = vec![];
+    let mut seen: HashSet<(&str, &Path)> = HashSet::new();
+    for processed_file in processed_files {
+        for definition in &processed_file.definitions {
+            let name = definition.fully_qualified_name.as_str();
+            if inferred_constant_names.contains(name)
+                || !seen.insert((name, &processed_file.absolute_path))
+            {
+                continue;
+            }
+            parsed_constants.push(ConstantDefinition {
+                fully_qualified_name: name.to_owned(),
+                absolute_path_of_definition: processed_file.absolute_path.to_owned(),
+                implicit_namespace: false,
+            });
+        }
+    }
+    parsed_constants
 }
 
 fn inferred_constant_from_file(
     absolute_path: &Path,
-    absolute_autoload_path: &PathBuf,
-    acronyms: &HashSet<String>,
+    constant_path: &Path,
+    inflections: &Inflections,
     default_namespace: &String,
 ) -> ConstantDefinition {
-    let relative_path = absolute_path.strip_prefix(absolute_autoload_path).unwrap();
-
-    let relative_path = relative_path.with_extension("");
-
-    let relative_path_str = relative_path.to_str().unwrap();
-    let camelized_path = inflector_shim::camelize(relative_path_str, acronyms);
+    let constant_path_str = constant_path.to_string_lossy();
+    let camelized_path = inflector_shim::camelize(&constant_path_str, inflections);
     let fully_qualified_name = format!("{}::{}", default_namespace, camelized_path);
 
     ConstantDefinition {
         fully_qualified_name,
         absolute_path_of_definition: absolute_path.to_path_buf(),
+        implicit_namespace: false,
     }
 }
 
@@ -139,7 +336,8 @@ mod tests {
             vec![ConstantDefinition {
                 fully_qualified_name: "::Foo".to_string(),
                 absolute_path_of_definition: get_absolute_root(SIMPLE_APP)
-                    .join("packs/foo/app/services/foo.rb")
+                    .join("packs/foo/app/services/foo.rb"),
+                implicit_namespace: false,
             }],
             get_zeitwerk_constant_resolver_for_fixture(SIMPLE_APP)
                 .unwrap()
@@ -154,7 +352,8 @@ mod tests {
             vec![ConstantDefinition {
                 fully_qualified_name: "::Company::Widget".to_string(),
                 absolute_path_of_definition: get_absolute_root(SIMPLE_APP)
-                    .join("app/company_data/widget.rb")
+                    .join("app/company_data/widget.rb"),
+                implicit_namespace: false,
             }],
             get_zeitwerk_constant_resolver_for_fixture(SIMPLE_APP)
                 .unwrap()
@@ -171,7 +370,8 @@ mod tests {
         assert_eq!(
             vec![ConstantDefinition {
                 fully_qualified_name: "::Foo".to_string(),
-                absolute_path_of_definition: absolute_root.join("packs/foo/app/services/foo.rb")
+                absolute_path_of_definition: absolute_root.join("packs/foo/app/services/foo.rb"),
+                implicit_namespace: false,
             }],
             resolver
                 .resolve(&String::from("Foo"), &["Foo", "Bar", "Baz"])
@@ -187,7 +387,8 @@ mod tests {
             vec![ConstantDefinition {
                 fully_qualified_name: "::Foo::Bar".to_string(),
                 absolute_path_of_definition: absolute_root
-                    .join("packs/foo/app/services/foo/bar.rb")
+                    .join("packs/foo/app/services/foo/bar.rb"),
+                implicit_namespace: false,
             }],
             resolver.resolve("Bar", &["Foo"]).unwrap()
         );
@@ -201,7 +402,8 @@ mod tests {
         assert_eq!(
             vec![ConstantDefinition {
                 fully_qualified_name: "::Bar".to_string(),
-                absolute_path_of_definition: absolute_root.join("packs/bar/app/services/bar.rb")
+                absolute_path_of_definition: absolute_root.join("packs/bar/app/services/bar.rb"),
+                implicit_namespace: false,
             }],
             resolver.resolve("::Bar", &["Foo"]).unwrap()
         );
@@ -214,7 +416,8 @@ mod tests {
         assert_eq!(
             vec![ConstantDefinition {
                 fully_qualified_name: "::Bar::BAR".to_string(),
-                absolute_path_of_definition: absolute_root.join("packs/bar/app/services/bar.rb")
+                absolute_path_of_definition: absolute_root.join("packs/bar/app/services/bar.rb"),
+                implicit_namespace: false,
             }],
             resolver.resolve(&String::from("::Bar::BAR"), &[]).unwrap()
         );
@@ -230,7 +433,8 @@ mod tests {
             vec![ConstantDefinition {
                 fully_qualified_name: "::MyModule::SomeAPIClass".to_string(),
                 absolute_path_of_definition: absolute_root
-                    .join("app/services/my_module/some_api_class.rb")
+                    .join("app/services/my_module/some_api_class.rb"),
+                implicit_namespace: false,
             }],
             resolver
                 .resolve(&String::from("::MyModule::SomeAPIClass"), &[])
@@ -241,7 +445,8 @@ mod tests {
             vec![ConstantDefinition {
                 fully_qualified_name: "::MyModule::SomeCSVClass".to_string(),
                 absolute_path_of_definition: absolute_root
-                    .join("app/services/my_module/some_csv_class.rb")
+                    .join("app/services/my_module/some_csv_class.rb"),
+                implicit_namespace: false,
             }],
             resolver
                 .resolve(&String::from("::MyModule::SomeCSVClass"), &[])
@@ -249,6 +454,135 @@ mod tests {
         );
     }
 
+    #[test]
+    fn collapsed_and_ignored_directories() {
+        let app = "tests/fixtures/app_with_zeitwerk_rules";
+        let absolute_root = get_absolute_root(app);
+        let resolver = get_zeitwerk_constant_resolver_for_fixture(app).unwrap();
+        let mut constants = resolver
+            .fully_qualified_constant_name_to_constant_definition_map()
+            .values()
+            .flatten()
+            .map(|constant| {
+                (
+                    constant.fully_qualified_name.as_str(),
+                    constant
+                        .absolute_path_of_definition
+                        .strip_prefix(&absolute_root)
+                        .unwrap()
+                        .to_str()
+                        .unwrap(),
+                )
+            })
+            .collect::<Vec<(&str, &str)>>();
+        constants.sort();
+
+        assert_eq!(
+            vec![
+                (
+                    "::ButtonComponent",
+                    "packs/ui/app/components/button/button_component.rb"
+                ),
+                (
+                    "::ButtonPreview",
+                    "packs/ui/app/components/button/button_preview.rb"
+                ),
+                ("::Generators", "app/services/generators"),
+                (
+                    "::Generators::ModelGenerator",
+                    "app/services/generators/model_generator.rb"
+                ),
+                ("::Payments", "app/services/payments"),
+                ("::Payments::Stripe", "app/services/payments/stripe"),
+                (
+                    "::Payments::Stripe::Client",
+                    "app/services/payments/stripe/client.rb"
+                ),
+            ],
+            constants
+        );
+    }
+
+    #[test]
+    fn implicit_namespace() {
+        let app = "tests/fixtures/app_with_zeitwerk_rules";
+        let absolute_root = get_absolute_root(app);
+        let resolver = get_zeitwerk_constant_resolver_for_fixture(app).unwrap();
+
+        assert_eq!(
+            vec![ConstantDefinition {
+                fully_qualified_name: "::Payments::Stripe".to_string(),
+                absolute_path_of_definition: absolute_root.join("app/services/payments/stripe"),
+                implicit_namespace: true,
+            }],
+            resolver.resolve("Stripe", &["Payments"]).unwrap()
+        );
+        assert_eq!(
+            vec![ConstantDefinition {
+                fully_qualified_name: "::Payments::Stripe::Client".to_string(),
+                absolute_path_of_definition: absolute_root
+                    .join("app/services/payments/stripe/client.rb"),
+                implicit_namespace: false,
+            }],
+            resolver.resolve("Stripe::Client", &["Payments"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn constant_defined_in_multiple_files() {
+        let app = "tests/fixtures/app_with_duplicate_definitions";
+        let absolute_root = get_absolute_root(app);
+        let resolver = get_zeitwerk_constant_resolver_for_fixture(app).unwrap();
+
+        // packs/patches/app/models/widget.rb is an ignored monkeypatch
+        assert_eq!(
+            vec![
+                ConstantDefinition {
+                    fully_qualified_name: "::Widget".to_string(),
+                    absolute_path_of_definition: absolute_root
+                        .join("packs/bar/app/models/widget.rb"),
+                    implicit_namespace: false,
+                },
+                ConstantDefinition {
+                    fully_qualified_name: "::Widget".to_string(),
+                    absolute_path_of_definition: absolute_root
+                        .join("packs/baz/app/models/widget.rb"),
+                    implicit_namespace: false,
+                },
+            ],
+            resolver.resolve("Widget", &["Order"]).unwrap()
+        );
+        assert_eq!(
+            3,
+            resolver.fully_qualified_constant_name_to_constant_definition_map()["::Widget"].len()
+        );
+    }
+
+    #[test]
+    fn constant_only_defined_by_ignored_monkeypatches() {
+        let patch = PathBuf::from("/app/packs/patches/app/models/string.rb");
+        let resolver = constant_resolver::ZeitwerkConstantResolver::create(
+            vec![
+                ConstantDefinition {
+                    fully_qualified_name: "::Patches".to_string(),
+                    absolute_path_of_definition: PathBuf::from(
+                        "/app/packs/patches/app/models/patches.rb",
+                    ),
+                    implicit_namespace: false,
+                },
+                ConstantDefinition {
+                    fully_qualified_name: "::Patches::String".to_string(),
+                    absolute_path_of_definition: patch.clone(),
+                    implicit_namespace: false,
+                },
+            ],
+            &HashSet::from([patch]),
+        );
+
+        assert_eq!(None, resolver.resolve("String", &["Patches"]));
+        assert_eq!(None, resolver.resolve("::Patches::String::Shout", &[]));
+    }
+
     #[test]
     fn test_file_map() {
         let constant_resolver = get_zeitwerk_constant_resolver_for_fixture(SIMPLE_APP).unwrap();
@@ -263,6 +597,7 @@ mod tests {
                 fully_qualified_name: "::Foo::Bar".to_owned(),
                 absolute_path_of_definition: absolute_root
                     .join("packs/foo/app/services/foo/bar.rb"),
+                implicit_namespace: false,
             }],
         );
 
@@ -271,6 +606,7 @@ mod tests {
             vec![ConstantDefinition {
                 fully_qualified_name: "::Bar".to_owned(),
                 absolute_path_of_definition: absolute_root.join("packs/bar/app/services/bar.rb"),
+                implicit_namespace: false,
             }],
         );
         expected_constant_map.insert(
@@ -278,6 +614,7 @@ mod tests {
             vec![ConstantDefinition {
                 fully_qualified_name: "::Baz".to_owned(),
                 absolute_path_of_definition: absolute_root.join("packs/baz/app/services/baz.rb"),
+                implicit_namespace: false,
             }],
         );
         expected_constant_map.insert(
@@ -285,6 +622,7 @@ mod tests {
             vec![ConstantDefinition {
                 fully_qualified_name: "::Foo".to_owned(),
                 absolute_path_of_definition: absolute_root.join("packs/foo/app/services/foo.rb"),
+                implicit_namespace: false,
             }],
         );
         expected_constant_map.insert(
@@ -293,6 +631,7 @@ mod tests {
                 fully_qualified_name: "::SomeConcern".to_owned(),
                 absolute_path_of_definition: absolute_root
                     .join("packs/bar/app/models/concerns/some_concern.rb"),
+                implicit_namespace: false,
             }],
         );
         expected_constant_map.insert(
@@ -300,6 +639,7 @@ mod tests {
             vec![ConstantDefinition {
                 fully_qualified_name: "::SomeRootClass".to_owned(),
                 absolute_path_of_definition: absolute_root.join("app/services/some_root_class.rb"),
+                implicit_namespace: false,
             }],
         );
         expected_constant_map.insert(
@@ -307,6 +647,7 @@ mod tests {
             vec![ConstantDefinition {
                 fully_qualified_name: "::Company::Widget".to_owned(),
                 absolute_path_of_definition: absolute_root.join("app/company_data/widget.rb"),
+                implicit_namespace: false,
             }],
         );
 
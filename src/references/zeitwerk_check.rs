@@ -0,0 +1,301 @@
+use std::{collections::HashMap, fmt, path::PathBuf};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+
+use crate::references::{
+    configuration::Configuration,
+    constant_resolver::ConstantDefinition,
+    error::ReferencesError,
+    parser::{parse, ParsedDefinition, SourceLocation},
+    zeitwerk::autoloaded_constants,
+};
+
+/// A file whose definitions do not match what Zeitwerk expects from its path.
+/// These are the errors `bin/rails zeitwerk:check` reports when eager loading.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum ZeitwerkViolation {
+    // The file defines constants, but not the one its path implies
+    MismatchedName {
+        relative_file: String,
+        expected_constant: String,
+        defined_constants: Vec<String>,
+    },
+    // The file defines the expected constant, and also a constant outside of it
+    ExtraConstant {
+        relative_file: String,
+        expected_constant: String,
+        constant: String,
+        source_location: SourceLocation,
+    },
+    // The file defines no class, module or constant at all
+    NoDefinition {
+        relative_file: String,
+        expected_constant: String,
+    },
+}
+
+impl ZeitwerkViolation {
+    pub fn relative_file(&self) -> &str {
+        match self {
+            ZeitwerkViolation::MismatchedName { relative_file, .. }
+            | ZeitwerkViolation::ExtraConstant { relative_file, .. }
+            | ZeitwerkViolation::NoDefinition { relative_file, .. } => relative_file,
+        }
+    }
+}
+
+impl fmt::Display for ZeitwerkViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZeitwerkViolation::MismatchedName {
+                relative_file,
+                expected_constant,
+                defined_constants,
+            } => write!(
+                f,
+                "expected file {} to define constant {}, but it defines {}",
+                relative_file,
+                expected_constant,
+                defined_constants.join(", ")
+            ),
+            ZeitwerkViolation::ExtraConstant {
+                relative_file,
+                expected_constant,
+                constant,
+                source_location,
+            } => write!(
+                f,
+                "{}:{} defines {}, which is not within {}",
+                relative_file, source_location.line, constant, expected_constant
+            ),
+            ZeitwerkViolation::NoDefinition {
+                relative_file,
+                expected_constant,
+            } => write!(
+                f,
+                "expected file {} to define constant {}, but it defines nothing",
+                relative_file, expected_constant
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ZeitwerkCheckReport {
+    pub checked_files: usize,
+    // sorted by file
+    pub violations: Vec<ZeitwerkViolation>,
+}
+
+impl ZeitwerkCheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl fmt::Display for ZeitwerkCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for violation in &self.violations {
+            writeln!(f, "{}", violation)?;
+        }
+        if self.is_ok() {
+            write!(f, "All is good! ({} files checked)", self.checked_files)
+        } else {
+            write!(
+                f,
+                "{} errors found ({} files checked)",
+                self.violations.len(),
+                self.checked_files
+            )
+        }
+    }
+}
+
+/// Statically verifies that every autoloaded file defines the constant Zeitwerk infers from
+/// its path, like `bin/rails zeitwerk:check` does by eager loading the application.
+/// Autoloaded files outside of `included_files` are not checked.
+pub fn zeitwerk_check(
+    configuration: &Configuration,
+) -> Result<ZeitwerkCheckReport, ReferencesError> {
+    let definitions_by_file = parse(configuration)?
+        .into_iter()
+        .map(|processed_file| (processed_file.absolute_path, processed_file.definitions))
+        .collect::<HashMap<PathBuf, Vec<ParsedDefinition>>>();
+    let autoloaded_constants = autoloaded_constants(configuration)?
+        .into_iter()
+        .filter(|constant| definitions_by_file.contains_key(&constant.absolute_path_of_definition))
+        .collect::<Vec<ConstantDefinition>>();
+
+    let violations = autoloaded_constants
+        .par_iter()
+        .map(|constant| {
+            check_file(
+                configuration,
+                constant,
+                &definitions_by_file[&constant.absolute_path_of_definition],
+            )
+        })
+        .collect::<anyhow::Result<Vec<Vec<ZeitwerkViolation>>>>()?;
+    let mut violations = violations.into_iter().flatten().collect::<Vec<_>>();
+    violations.sort_by(|a, b| a.relative_file().cmp(b.relative_file()));
+
+    Ok(ZeitwerkCheckReport {
+        checked_files: autoloaded_constants.len(),
+        violations,
+    })
+}
+
+fn check_file(
+    configuration: &Configuration,
+    constant: &ConstantDefinition,
+    definitions: &[ParsedDefinition],
+) -> anyhow::Result<Vec<ZeitwerkViolation>> {
+    let relative_file = configuration.relative_path(&constant.absolute_path_of_definition)?;
+    let expected_constant = constant.fully_qualified_name.to_owned();
+
+    if definitions.is_empty() {
+        return Ok(vec![ZeitwerkViolation::NoDefinition {
+            relative_file,
+            expected_constant,
+        }]);
+    }
+
+    if !definitions
+        .iter()
+        .any(|definition| definition.fully_qualified_name == expected_constant)
+    {
+        return Ok(vec![ZeitwerkViolation::MismatchedName {
+            relative_file,
+            defined_constants: outermost_definitions(definitions, &expected_constant)
+                .iter()
+                .map(|definition| definition.fully_qualified_name.to_owned())
+                .collect(),
+            expected_constant,
+        }]);
+    }
+
+    Ok(outermost_definitions(definitions, &expected_constant)
+        .into_iter()
+        .filter(|definition| !is_within(&expected_constant, &definition.fully_qualified_name))
+        .map(|definition| ZeitwerkViolation::ExtraConstant {
+            relative_file: relative_file.to_owned(),
+            expected_constant: expected_constant.to_owned(),
+            constant: definition.fully_qualified_name.to_owned(),
+            source_location: SourceLocation {
+                line: definition.location.start_row,
+                column: definition.location.start_col,
+            },
+        })
+        .collect())
+}
+
+// Definitions other than the namespaces that enclose the expected constant, e.g. `::Company`
+// for `::Company::Widget`, dropping those nested in another returned definition
+fn outermost_definitions<'a>(
+    definitions: &'a [ParsedDefinition],
+    expected_constant: &str,
+) -> Vec<&'a ParsedDefinition> {
+    let candidates = definitions
+        .iter()
+        .filter(|definition| !is_within(&definition.fully_qualified_name, expected_constant))
+        .collect::<Vec<&ParsedDefinition>>();
+
+    let mut outermost: Vec<&ParsedDefinition> = vec![];
+    for candidate in &candidates {
+        let is_nested = candidates.iter().any(|other| {
+            other.fully_qualified_name != candidate.fully_qualified_name
+                && is_within(&other.fully_qualified_name, &candidate.fully_qualified_name)
+        });
+        let is_repeated = outermost
+            .iter()
+            .any(|other| other.fully_qualified_name == candidate.fully_qualified_name);
+        if !is_nested && !is_repeated {
+            outermost.push(candidate);
+        }
+    }
+    outermost
+}
+
+// Whether `constant` is `namespace` itself or nested in it
+fn is_within(namespace: &str, constant: &str) -> bool {
+    constant == namespace
+        || constant
+            .strip_prefix(namespace)
+            .is_some_and(|rest| rest.starts_with("::"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::references::common_test::common_test::{configuration_for_fixture, SIMPLE_APP};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn empty_file() -> anyhow::Result<()> {
+        let report = zeitwerk_check(&configuration_for_fixture(SIMPLE_APP, false))?;
+        assert_eq!(
+            report.violations,
+            vec![ZeitwerkViolation::NoDefinition {
+                relative_file: String::from("packs/baz/app/services/baz.rb"),
+                expected_constant: String::from("::Baz"),
+            }]
+        );
+        assert_eq!(report.checked_files, 7);
+        assert!(!report.is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn reports_violations() -> anyhow::Result<()> {
+        let configuration =
+            configuration_for_fixture("tests/fixtures/app_with_zeitwerk_errors", false);
+        let report = zeitwerk_check(&configuration)?;
+
+        assert_eq!(
+            report.violations,
+            vec![
+                ZeitwerkViolation::NoDefinition {
+                    relative_file: String::from("app/models/empty.rb"),
+                    expected_constant: String::from("::Empty"),
+                },
+                ZeitwerkViolation::ExtraConstant {
+                    relative_file: String::from("app/models/user.rb"),
+                    expected_constant: String::from("::User"),
+                    constant: String::from("::UserHelper"),
+                    source_location: SourceLocation { line: 6, column: 6 },
+                },
+                ZeitwerkViolation::MismatchedName {
+                    relative_file: String::from("app/models/widget.rb"),
+                    expected_constant: String::from("::Widget"),
+                    defined_constants: vec![String::from("::Gadget")],
+                },
+            ]
+        );
+        assert_eq!(
+            report.to_string(),
+            "\
+expected file app/models/empty.rb to define constant ::Empty, but it defines nothing
+app/models/user.rb:6 defines ::UserHelper, which is not within ::User
+expected file app/models/widget.rb to define constant ::Widget, but it defines ::Gadget
+3 errors found (5 files checked)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn skips_files_that_are_not_included() -> anyhow::Result<()> {
+        let mut configuration =
+            configuration_for_fixture("tests/fixtures/app_with_zeitwerk_errors", false);
+        let widget = configuration.absolute_root.join("app/models/widget.rb");
+        assert!(configuration.included_files.remove(&widget));
+
+        let report = zeitwerk_check(&configuration)?;
+        assert_eq!(report.checked_files, 4);
+        assert!(report
+            .violations
+            .iter()
+            .all(|violation| violation.relative_file() != "app/models/widget.rb"));
+        Ok(())
+    }
+}
@@ -0,0 +1,5 @@
+class Invoice
+  def customer
+    Customer.first
+  end
+end
@@ -0,0 +1,5 @@
+class Ledger
+  def invoices
+    Invoice.all
+  end
+end
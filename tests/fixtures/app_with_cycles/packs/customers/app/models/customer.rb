@@ -0,0 +1,5 @@
+class Customer
+  def orders
+    Order.where(customer_id: id)
+  end
+end
@@ -0,0 +1,9 @@
+class Order
+  def customer
+    Customer.find(customer_id)
+  end
+
+  def invoice
+    Invoice.new(customer: Customer.find(customer_id))
+  end
+end
@@ -0,0 +1,5 @@
+class Order
+  def widget
+    Widget.new
+  end
+end
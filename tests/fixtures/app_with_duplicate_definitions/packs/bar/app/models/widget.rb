@@ -0,0 +1,3 @@
+class Widget
+  def self.color = "blue"
+end
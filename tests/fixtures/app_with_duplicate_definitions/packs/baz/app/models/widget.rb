@@ -0,0 +1,3 @@
+class Widget
+  def self.size = 3
+end
@@ -0,0 +1,3 @@
+class Widget
+  def self.shape = "square"
+end
@@ -0,0 +1 @@
+Schema = 1
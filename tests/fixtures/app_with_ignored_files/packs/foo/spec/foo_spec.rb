@@ -0,0 +1,2 @@
+RSpec.describe Foo do
+end
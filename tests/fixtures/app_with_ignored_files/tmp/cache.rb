@@ -0,0 +1 @@
+Cache = 1
@@ -0,0 +1,4 @@
+module Billing
+  class Refund < Invoice
+  end
+end
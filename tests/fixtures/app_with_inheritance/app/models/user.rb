@@ -0,0 +1,3 @@
+class User < ApplicationRecord
+  ROLES = %w[member admin].freeze
+end
@@ -0,0 +1,15 @@
+class Report
+  LIMIT = 10
+
+  def self.build(klass)
+    klass::Builder.new(Legacy::Invoice)
+  end
+end
+
+class self::Anonymous
+end
+
+module concern::Helpers
+end
+
+record.class::LIMIT = 5
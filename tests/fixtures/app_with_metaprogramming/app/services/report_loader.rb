@@ -0,0 +1,7 @@
+class ReportLoader
+  def load(kind)
+    "Legacy::Invoice".constantize
+    Object.const_get(:Report)
+    "#{kind}Report".safe_constantize
+  end
+end
@@ -0,0 +1,4 @@
+module Legacy
+  class Invoice
+  end
+end
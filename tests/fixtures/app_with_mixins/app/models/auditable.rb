@@ -0,0 +1,3 @@
+module Auditable
+  include Loggable
+end
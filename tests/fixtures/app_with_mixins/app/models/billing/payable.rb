@@ -0,0 +1,5 @@
+module Billing
+  module Payable
+    include Auditable
+  end
+end
@@ -0,0 +1,7 @@
+class Order
+  include Auditable
+  include Billing::Payable
+  include Comparable
+  prepend Tracing
+  extend Searchable
+end
@@ -0,0 +1,3 @@
+module Searchable
+  include Loggable
+end
@@ -0,0 +1,2 @@
+module Tracing
+end
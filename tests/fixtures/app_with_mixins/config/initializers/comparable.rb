@@ -0,0 +1,4 @@
+# Reopens a module from the standard library, which does not resolve to a file
+module Comparable
+  include Loggable
+end
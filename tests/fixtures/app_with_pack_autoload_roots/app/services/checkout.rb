@@ -0,0 +1,7 @@
+class Checkout
+  def call
+    Payments::Charge.new
+    Payments::Gateways::Stripe.new
+    Payments::Refund.new
+  end
+end
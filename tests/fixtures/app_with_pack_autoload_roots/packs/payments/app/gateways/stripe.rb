@@ -0,0 +1,6 @@
+module Payments
+  module Gateways
+    class Stripe
+    end
+  end
+end
@@ -0,0 +1,4 @@
+module Payments
+  class Refund
+  end
+end
@@ -0,0 +1,6 @@
+class Checkout
+  def total
+    Order::STATUSES
+    Money::Currency.new(Money::Currency::DEFAULT)
+  end
+end
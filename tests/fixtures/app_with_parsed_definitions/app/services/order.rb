@@ -0,0 +1,3 @@
+class Order
+  STATUSES = %w[open closed].freeze
+end
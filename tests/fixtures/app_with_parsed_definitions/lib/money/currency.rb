@@ -0,0 +1,5 @@
+module Money
+  class Currency
+    DEFAULT = "USD"
+  end
+end
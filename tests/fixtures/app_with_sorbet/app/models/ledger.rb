@@ -0,0 +1,2 @@
+class Ledger
+end
@@ -0,0 +1,11 @@
+class Charge < T::Struct
+  extend T::Sig
+
+  prop :currency, Currency
+
+  sig { params(amount: Money).returns(T.nilable(Money)) }
+  def apply(amount)
+    ledger = T.let(Ledger.new, Ledger)
+    ledger.record(amount)
+  end
+end
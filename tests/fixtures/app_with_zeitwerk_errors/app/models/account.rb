@@ -0,0 +1,3 @@
+class Account
+  DEFAULT_PLAN = "free"
+end
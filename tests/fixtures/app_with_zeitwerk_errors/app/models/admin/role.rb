@@ -0,0 +1,4 @@
+module Admin
+  class Role
+  end
+end
@@ -0,0 +1 @@
+# TODO: move the empty-state logic here
@@ -0,0 +1,9 @@
+class User
+  class Error < StandardError
+  end
+end
+
+class UserHelper
+  class Inner
+  end
+end
@@ -0,0 +1,4 @@
+module Generators
+  class ModelGenerator
+  end
+end
@@ -0,0 +1,2 @@
+class <%= class_name %>
+end
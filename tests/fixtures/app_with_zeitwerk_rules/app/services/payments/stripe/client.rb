@@ -0,0 +1,6 @@
+module Payments
+  module Stripe
+    class Client
+    end
+  end
+end